@@ -0,0 +1,51 @@
+//! Generates the list of files in `assets/` for `src/assets.rs` to embed, so
+//! that adding an asset doesn't also mean adding it to a list by hand.
+
+use std::{
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let assets_dir = manifest_dir.join("assets");
+    println!("cargo:rerun-if-changed={}", assets_dir.display());
+
+    let mut files = Vec::new();
+    collect_files(&assets_dir, &mut files);
+    files.sort();
+
+    let mut generated = String::from("&[\n");
+    for file in files {
+        let relative = file.strip_prefix(&assets_dir).unwrap();
+        // Asset paths always use forward slashes, whatever the platform.
+        let asset_path = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        writeln!(
+            generated,
+            "    ({asset_path:?}, {file:?}, include_bytes!({file:?})),",
+            file = file.display().to_string(),
+        )
+        .unwrap();
+    }
+    generated.push(']');
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("assets.rs"), generated).unwrap();
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! How enemies decide what to do.
+//!
+//! Each kind of enemy has a list of [`Rule`]s in its definition. Every fixed
+//! step, an enemy does whatever the first rule whose condition holds says,
+//! like the selector at the root of a behavior tree. For example, an enemy
+//! that waits in ambush is
+//!
+//! ```ron
+//! behavior: [
+//!     (when: Sees, do: Chase),
+//!     (when: Always, do: Wait),
+//! ],
+//! ```
+//!
+//! What an enemy knows about the world is in its [`Perception`]: the nearest
+//! character it can see, and whether it has walked into a wall.
+
+use bevy::{
+    math::bounding::{Aabb2d, RayCast2d},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    character::Character,
+    enemy::{Defeated, Enemy, EnemyKinds},
+    health::Dead,
+    physics::{self, Collided, Collider, Collision, Grounded, PhysicsPosition, Sensor, Velocity},
+    projectile::Shoot,
+};
+
+/// Do something when a condition holds.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub when: Condition,
+    #[serde(rename = "do")]
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum Condition {
+    Always,
+    /// A character is in sight.
+    Sees,
+    /// A character is in sight and no further away than this.
+    Within(f32),
+    Not(Box<Condition>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Action {
+    /// Walk back and forth, turning at walls, and at ledges if the kind minds
+    /// them.
+    Patrol,
+    /// Head for the character in sight.
+    Chase,
+    /// Head away from the character in sight.
+    Flee,
+    /// Stand still.
+    Wait,
+    /// Stand still, facing the character in sight, and shoot at it every so
+    /// often, if its kind has a projectile.
+    Shoot { every: f32 },
+}
+
+/// What an enemy behaves like when its kind doesn't say.
+pub fn default_behavior() -> Vec<Rule> {
+    vec![Rule {
+        when: Condition::Always,
+        action: Action::Patrol,
+    }]
+}
+
+impl Condition {
+    fn holds(&self, perception: &Perception, position: Vec2) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::Sees => perception.target.is_some(),
+            Condition::Within(distance) => perception
+                .target
+                .is_some_and(|target| target.distance(position) <= *distance),
+            Condition::Not(condition) => !condition.holds(perception, position),
+        }
+    }
+}
+
+/// What an enemy knows about its surroundings, as of the latest fixed step.
+#[derive(Component, Default)]
+pub struct Perception {
+    /// Where the nearest character it can see is.
+    pub target: Option<Vec2>,
+    /// Which way, -1 or 1, there's a wall it has walked into.
+    pub wall: Option<f32>,
+}
+
+/// What an enemy is doing.
+#[derive(Component)]
+pub struct Brain {
+    pub action: Action,
+    /// Counts down to the next shot, while shooting.
+    pub cooldown: Timer,
+}
+
+impl Default for Brain {
+    fn default() -> Self {
+        Brain {
+            action: Action::Wait,
+            cooldown: Timer::default(),
+        }
+    }
+}
+
+/// Look for the nearest character in each enemy's sight range that isn't
+/// hidden behind anything solid.
+pub fn perceive(
+    mut enemies: Query<(&Enemy, &PhysicsPosition, &mut Perception), Without<Defeated>>,
+    characters: Query<&PhysicsPosition, (With<Character>, Without<Dead>)>,
+    solids: Query<
+        (&Transform, Option<&PhysicsPosition>, &Collider),
+        (Without<Grounded>, Without<Sensor>),
+    >,
+    kinds: Option<Res<EnemyKinds>>,
+) {
+    let Some(kinds) = kinds else {
+        return;
+    };
+    let solids: Vec<Aabb2d> = solids
+        .iter()
+        .map(|(transform, position, collider)| {
+            collider.aabb(physics::simulated_position(transform, position))
+        })
+        .collect();
+
+    for (enemy, position, mut perception) in &mut enemies {
+        let Some(kind) = kinds.get(&enemy.kind) else {
+            continue;
+        };
+        let eye = position.current;
+
+        perception.target = characters
+            .iter()
+            .map(|character| character.current)
+            .filter(|target| {
+                let distance = eye.distance(*target);
+                if distance > kind.definition.sight {
+                    return false;
+                }
+                let Ok(direction) = Dir2::new(*target - eye) else {
+                    return true;
+                };
+                let ray = RayCast2d::new(eye, direction, distance);
+                !solids
+                    .iter()
+                    .any(|solid| ray.aabb_intersection_at(solid).is_some())
+            })
+            .min_by(|a, b| eye.distance(*a).total_cmp(&eye.distance(*b)));
+    }
+}
+
+/// Pick what each enemy does, by the first of its kind's rules that holds.
+pub fn decide(
+    mut enemies: Query<(&Enemy, &PhysicsPosition, &Perception, &mut Brain), Without<Defeated>>,
+    kinds: Option<Res<EnemyKinds>>,
+) {
+    let Some(kinds) = kinds else {
+        return;
+    };
+
+    for (enemy, position, perception, mut brain) in &mut enemies {
+        let Some(kind) = kinds.get(&enemy.kind) else {
+            continue;
+        };
+        let action = kind
+            .definition
+            .behavior
+            .iter()
+            .find(|rule| rule.when.holds(perception, position.current))
+            .map_or(Action::Wait, |rule| rule.action);
+
+        if action != brain.action {
+            brain.action = action;
+            if let Action::Shoot { every } = action {
+                brain.cooldown = Timer::from_seconds(every, TimerMode::Repeating);
+            }
+        }
+    }
+}
+
+/// Carry out what each enemy has decided to do.
+///
+/// An enemy that would walk into a wall, or off a ledge if it minds them,
+/// turns around if it's patrolling. Otherwise it jumps, if its kind can, or
+/// stops where it is.
+pub fn act(
+    mut enemies: Query<
+        (
+            Entity,
+            &mut Enemy,
+            &mut Brain,
+            &Perception,
+            &PhysicsPosition,
+            &Collider,
+            &mut Velocity,
+            &mut Sprite,
+        ),
+        Without<Defeated>,
+    >,
+    ground: Query<
+        (&Transform, Option<&PhysicsPosition>, &Collider),
+        (Without<Grounded>, Without<Sensor>),
+    >,
+    kinds: Option<Res<EnemyKinds>>,
+    mut shots: EventWriter<Shoot>,
+    time: Res<Time>,
+) {
+    let Some(kinds) = kinds else {
+        return;
+    };
+
+    for (entity, mut enemy, mut brain, perception, position, collider, mut velocity, mut sprite) in
+        &mut enemies
+    {
+        let Some(kind) = kinds.get(&enemy.kind) else {
+            continue;
+        };
+        let toward_target = perception.target.map_or(enemy.facing, |target| {
+            (target.x - position.current.x).signum()
+        });
+
+        let mut direction = match brain.action {
+            Action::Patrol => enemy.facing,
+            Action::Chase => toward_target,
+            Action::Flee => -toward_target,
+            Action::Wait | Action::Shoot { .. } => 0.0,
+        };
+
+        if direction != 0.0 {
+            let walled = perception.wall == Some(direction);
+            let ledge = kind.definition.turns_at_ledges
+                && velocity.is_grounded
+                && !ground_ahead(collider.aabb(position.current), direction, &ground);
+            if walled || ledge {
+                match (brain.action, kind.definition.jump_velocity) {
+                    (Action::Patrol, _) => direction = -direction,
+                    (_, Some(jump)) if velocity.is_grounded => {
+                        velocity.y = jump;
+                        velocity.is_grounded = false;
+                    }
+                    _ => direction = 0.0,
+                }
+            }
+            if direction != 0.0 {
+                enemy.facing = direction;
+            }
+        }
+        velocity.x = direction * kind.definition.speed;
+
+        if let Action::Shoot { .. } = brain.action {
+            enemy.facing = toward_target;
+            if let Some(target) = perception.target {
+                if brain.cooldown.tick(time.delta()).just_finished() {
+                    let origin = position.current;
+                    let aim = Dir2::new(target - origin).unwrap_or(if enemy.facing > 0.0 {
+                        Dir2::X
+                    } else {
+                        Dir2::NEG_X
+                    });
+                    shots.send_batch(kind.shoot(entity, origin, aim));
+                }
+            }
+        }
+
+        // The pack's enemies face left.
+        sprite.flip_x = enemy.facing > 0.0;
+    }
+}
+
+/// Whether there's ground just below the front corner of a body's feet.
+fn ground_ahead(
+    body: Aabb2d,
+    direction: f32,
+    ground: &Query<
+        (&Transform, Option<&PhysicsPosition>, &Collider),
+        (Without<Grounded>, Without<Sensor>),
+    >,
+) -> bool {
+    let front = if direction > 0.0 {
+        body.max.x + 1.0
+    } else {
+        body.min.x - 1.0
+    };
+    let ahead = Vec2::new(front, body.min.y - 1.0);
+    ground.iter().any(|(transform, position, collider)| {
+        let platform = collider.aabb(physics::simulated_position(transform, position));
+        platform.min.cmple(ahead).all() && ahead.cmple(platform.max).all()
+    })
+}
+
+/// Note walls that enemies have walked into.
+pub fn sense_walls(
+    mut collided: EventReader<Collided>,
+    mut enemies: Query<&mut Perception, Without<Defeated>>,
+) {
+    for mut perception in &mut enemies {
+        perception.wall = None;
+    }
+    for collision in collided.read() {
+        let Ok(mut perception) = enemies.get_mut(collision.entity) else {
+            continue;
+        };
+        match collision.side {
+            Collision::Left => perception.wall = Some(1.0),
+            Collision::Right => perception.wall = Some(-1.0),
+            Collision::Top | Collision::Bottom => {}
+        }
+    }
+}
@@ -1,10 +1,71 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 
-#[derive(Component)]
+#[derive(Component, Debug, Clone, Copy)]
 pub struct AnimationIndices {
     pub idle: (usize, usize),
     pub run: (usize, usize),
+    pub hit: (usize, usize),
 }
 
 #[derive(Component, Deref, DerefMut)]
 pub struct AnimationTimer(pub Timer);
+
+/// A character's animations, as written in its `.anim.ron` file.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct CharacterAnimationDefinition {
+    /// The width and height of every frame, in pixels.
+    pub frame_size: u32,
+    /// How long each frame is shown for.
+    pub frame_seconds: f32,
+    pub idle: SpriteSheet,
+    pub run: SpriteSheet,
+    /// Getting hurt, which loops for as long as the character is hurt.
+    pub hit: SpriteSheet,
+    /// The path to a single image, rather than a sheet.
+    pub jump: String,
+    /// A tint over every frame.
+    #[serde(default = "default_color")]
+    pub color: Color,
+}
+
+fn default_color() -> Color {
+    Color::WHITE
+}
+
+/// A row of frames in one image.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteSheet {
+    /// The image's path under `assets/`.
+    pub texture: String,
+    pub frames: u32,
+}
+
+/// A sprite that steps through every frame of its texture atlas, over and
+/// over, for things that aren't characters.
+#[derive(Component)]
+pub struct LoopingAnimation {
+    pub frames: usize,
+    pub timer: Timer,
+}
+
+impl LoopingAnimation {
+    pub fn new(frames: usize, frame_seconds: f32) -> Self {
+        LoopingAnimation {
+            frames,
+            timer: Timer::from_seconds(frame_seconds, TimerMode::Repeating),
+        }
+    }
+}
+
+pub fn animate_loops(time: Res<Time>, mut query: Query<(&mut LoopingAnimation, &mut Sprite)>) {
+    for (mut animation, mut sprite) in &mut query {
+        animation.timer.tick(time.delta());
+        if !animation.timer.just_finished() {
+            continue;
+        }
+        if let Some(atlas) = &mut sprite.texture_atlas {
+            atlas.index = (atlas.index + 1) % animation.frames;
+        }
+    }
+}
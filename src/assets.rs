@@ -0,0 +1,42 @@
+//! Where the game's assets are loaded from.
+//!
+//! Normally every file under `assets/` is built into the executable, so that
+//! the game is a single file. With the `dev` feature, assets are read from the
+//! `assets/` directory instead and reloaded when they change on disk.
+//!
+//! Either way, load assets through [`asset_path`] so that they're looked for in
+//! the right place.
+
+use bevy::{asset::AssetPath, prelude::*};
+
+/// The path to load an asset from, given its path under `assets/`.
+pub fn asset_path<'a>(path: impl Into<AssetPath<'a>>) -> AssetPath<'a> {
+    let path = path.into();
+    if cfg!(feature = "dev") {
+        path
+    } else {
+        path.with_source("embedded")
+    }
+}
+
+pub struct EmbeddedAssetPlugin;
+
+impl Plugin for EmbeddedAssetPlugin {
+    #[cfg(feature = "dev")]
+    fn build(&self, _app: &mut App) {}
+
+    #[cfg(not(feature = "dev"))]
+    fn build(&self, app: &mut App) {
+        use bevy::asset::io::embedded::EmbeddedAssetRegistry;
+        use std::path::{Path, PathBuf};
+
+        /// `(asset path, full path, contents)` for every file under `assets/`,
+        /// generated by `build.rs`.
+        const ASSETS: &[(&str, &str, &[u8])] = include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+        let registry = app.world_mut().resource_mut::<EmbeddedAssetRegistry>();
+        for (asset_path, full_path, contents) in ASSETS {
+            registry.insert_asset(PathBuf::from(full_path), Path::new(asset_path), *contents);
+        }
+    }
+}
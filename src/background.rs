@@ -0,0 +1,225 @@
+//! Tiled, parallax-scrolling backgrounds.
+//!
+//! A level spawns a [`ParallaxBackground`] listing its layers, furthest away
+//! first. Each layer is a tiled sprite that is kept just big enough to cover
+//! the camera's view, and is slid along by less than the camera moves (or not
+//! at all) so that it looks further away than the level. Since the tiles
+//! repeat, this goes on forever in every direction. Layers can also drift on
+//! their own, like the backgrounds in the asset pack's previews do.
+
+use std::str::FromStr;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    assets::asset_path, camera::CharacterCamera, loading::LoadingAssets, pixel_perfect::PIXEL_SCALE,
+};
+
+/// The background tiles in `assets/bg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Background {
+    Blue,
+    Brown,
+    Gray,
+    Green,
+    Pink,
+    Purple,
+    Yellow,
+}
+
+impl Background {
+    /// The tile's path under `assets/`.
+    pub fn path(self) -> &'static str {
+        match self {
+            Background::Blue => "bg/blue.png",
+            Background::Brown => "bg/brown.png",
+            Background::Gray => "bg/gray.png",
+            Background::Green => "bg/green.png",
+            Background::Pink => "bg/pink.png",
+            Background::Purple => "bg/purple.png",
+            Background::Yellow => "bg/yellow.png",
+        }
+    }
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "blue" => Ok(Background::Blue),
+            "brown" => Ok(Background::Brown),
+            "gray" | "grey" => Ok(Background::Gray),
+            "green" => Ok(Background::Green),
+            "pink" => Ok(Background::Pink),
+            "purple" => Ok(Background::Purple),
+            "yellow" => Ok(Background::Yellow),
+            _ => Err(format!("unknown background {s:?}")),
+        }
+    }
+}
+
+/// A background to use for every layer of every level instead of what the
+/// level asks for.
+#[derive(Resource, Debug, Default)]
+pub struct BackgroundOverride(pub Option<Background>);
+
+impl BackgroundOverride {
+    /// Pass `--background <color>` on the command line to set it.
+    pub fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let Some(color) = args
+            .windows(2)
+            .find(|pair| pair[0] == "--background")
+            .map(|pair| &pair[1])
+        else {
+            return BackgroundOverride(None);
+        };
+
+        match color.parse() {
+            Ok(background) => BackgroundOverride(Some(background)),
+            Err(err) => {
+                warn!("Ignoring --background: {err}");
+                BackgroundOverride(None)
+            }
+        }
+    }
+}
+
+/// How fast background layers drift by default, in world units per second.
+const DRIFT: Vec2 = Vec2::new(-15.0, -15.0);
+
+/// A single tiled layer of a [`ParallaxBackground`].
+///
+/// In a level file, only `tile` and `parallax` have to be given.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackgroundLayer {
+    pub tile: Background,
+    /// How much the layer moves along with the camera. At 1.0 it's stuck to
+    /// the camera, as though infinitely far away; at 0.0 it scrolls past like
+    /// the level does.
+    pub parallax: f32,
+    /// How much to scale each tile up by.
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Multiplied with the image, e.g. to make a near layer see-through.
+    #[serde(default = "default_color")]
+    pub color: Color,
+    /// How fast the tiles drift by on their own, in world units per second.
+    #[serde(default = "default_drift")]
+    pub drift: Vec2,
+}
+
+fn default_scale() -> f32 {
+    PIXEL_SCALE
+}
+
+fn default_color() -> Color {
+    Color::WHITE
+}
+
+fn default_drift() -> Vec2 {
+    DRIFT
+}
+
+/// The background of a level. Its layers are spawned as children of it.
+#[derive(Component, Debug)]
+pub struct ParallaxBackground(pub Vec<BackgroundLayer>);
+
+/// A spawned [`BackgroundLayer`].
+#[derive(Component)]
+pub struct ParallaxLayer {
+    parallax: f32,
+    scale: f32,
+    drift: Vec2,
+    /// How far the layer has drifted so far.
+    drifted: Vec2,
+}
+
+/// How far back the furthest layer is drawn. The rest are in front of it.
+const BACKGROUND_Z: f32 = -10.0;
+
+pub fn spawn_background_layers(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    background_override: Res<BackgroundOverride>,
+    mut loading: Option<ResMut<LoadingAssets>>,
+    backgrounds: Query<(Entity, &ParallaxBackground), Added<ParallaxBackground>>,
+) {
+    for (entity, background) in &backgrounds {
+        commands
+            .entity(entity)
+            .insert((Transform::default(), Visibility::default()))
+            .with_children(|parent| {
+                for (index, layer) in background.0.iter().enumerate() {
+                    #[allow(clippy::cast_precision_loss)]
+                    let z = BACKGROUND_Z + index as f32 * 0.1;
+                    let tile = background_override.0.unwrap_or(layer.tile);
+                    let image = asset_server.load(asset_path(tile.path()));
+                    if let Some(loading) = &mut loading {
+                        loading.add(image.clone());
+                    }
+                    parent.spawn((
+                        Sprite {
+                            image,
+                            image_mode: SpriteImageMode::Tiled {
+                                tile_x: true,
+                                tile_y: true,
+                                stretch_value: layer.scale,
+                            },
+                            color: layer.color,
+                            ..default()
+                        },
+                        Transform::from_translation(Vec3::new(0.0, 0.0, z)),
+                        ParallaxLayer {
+                            parallax: layer.parallax,
+                            scale: layer.scale,
+                            drift: layer.drift,
+                            drifted: Vec2::ZERO,
+                        },
+                    ));
+                }
+            });
+    }
+}
+
+/// Keep every layer covering the camera's view, scrolled by its parallax.
+///
+/// Each layer is only ever moved by whole tiles relative to where it should
+/// be, which can't be seen since the tiles repeat. It's also kept a whole,
+/// even number of tiles in size, so that resizing it doesn't shift the tiles
+/// either.
+pub fn scroll_background(
+    camera: Query<(&Transform, &OrthographicProjection), With<CharacterCamera>>,
+    mut layers: Query<(&mut ParallaxLayer, &mut Sprite, &mut Transform), Without<CharacterCamera>>,
+    images: Res<Assets<Image>>,
+    time: Res<Time>,
+) {
+    let Ok((camera_transform, projection)) = camera.get_single() else {
+        return;
+    };
+    let camera_position = camera_transform.translation.truncate();
+    let view_size = projection.area.size();
+
+    for (mut layer, mut sprite, mut transform) in &mut layers {
+        let Some(image) = images.get(&sprite.image) else {
+            continue;
+        };
+        let tile_size = image.size_f32() * layer.scale;
+
+        // Wrap around so that this never grows big enough to lose precision.
+        let drift = layer.drift * time.delta_secs();
+        layer.drifted = (layer.drifted + drift).rem_euclid(tile_size);
+
+        // One tile of slack on every side, which also covers the corners
+        // when the camera is tilted by screen shake.
+        let tiles = (view_size / tile_size / 2.0).ceil() * 2.0 + 2.0;
+        sprite.custom_size = Some(tiles * tile_size);
+
+        let scrolled = camera_position * (1.0 - layer.parallax) - layer.drifted;
+        let offset = scrolled.rem_euclid(tile_size);
+        transform.translation.x = camera_position.x - offset.x;
+        transform.translation.y = camera_position.y - offset.y;
+    }
+}
@@ -0,0 +1,830 @@
+//! Blocks that react to being run into: boxes that break, blocks that are
+//! bumped from below, trampolines, and platforms that fall once stood on.
+//!
+//! They're all solid, so they react to the [`Collided`] events that
+//! characters and enemies send when they run into them, going by which side
+//! was hit and how fast.
+//!
+//! Boxes and bumped blocks can hold a fruit. It's spawned with the level, so
+//! that it counts towards the level's total, but stays [`Boxed`] until its
+//! block lets it out.
+
+use bevy::{ecs::system::SystemParam, prelude::*, sprite::Anchor};
+use serde::Deserialize;
+
+use crate::{
+    animation::LoopingAnimation,
+    assets::asset_path,
+    character::Character,
+    enemy::{Defeated, Enemy},
+    fruit::{self, FruitDefinition, FruitKind},
+    level::{KillHeight, LevelEntity, LevelKey, LevelState},
+    loading::LoadingAssets,
+    physics::{Collided, Collider, Collision, PhysicsPosition, Velocity},
+    pixel_perfect::PIXEL_SCALE,
+    platform::Platform,
+    restart::RestartPolicy,
+};
+
+/// A block, as written in a level file.
+#[derive(Component, Debug, Clone, Deserialize)]
+pub enum BlockDefinition {
+    /// A box centered on `position` that breaks once it has been hit `hits`
+    /// times, from below or by landing on it, letting out what it `drops`.
+    Box {
+        position: Vec2,
+        #[serde(default = "default_hits")]
+        hits: u32,
+        #[serde(default)]
+        drops: Option<FruitKind>,
+        #[serde(default)]
+        restart: RestartPolicy,
+    },
+    /// A block centered on `position` that jumps when it's hit from below,
+    /// knocking out enemies standing on it. The first bump lets out what it
+    /// `drops`, on top of it.
+    Bump {
+        position: Vec2,
+        #[serde(default)]
+        drops: Option<FruitKind>,
+        #[serde(default)]
+        restart: RestartPolicy,
+    },
+    /// A trampoline standing on `position`, which sends whatever lands on it
+    /// up at `launch` units per second.
+    Trampoline {
+        position: Vec2,
+        #[serde(default = "default_launch")]
+        launch: f32,
+    },
+    /// A platform centered on `position` that falls `delay` seconds after
+    /// somebody first stands on it, taking whoever is still on it down too.
+    /// Once it has fallen, it's gone until the level is reset.
+    Falling {
+        position: Vec2,
+        #[serde(default = "default_fall_delay")]
+        delay: f32,
+        #[serde(default)]
+        restart: RestartPolicy,
+    },
+}
+
+impl BlockDefinition {
+    /// What happens to the block when the level is reset, if it can be dealt
+    /// with at all.
+    pub fn restart(&self) -> Option<RestartPolicy> {
+        match self {
+            BlockDefinition::Box { restart, .. }
+            | BlockDefinition::Bump { restart, .. }
+            | BlockDefinition::Falling { restart, .. } => Some(*restart),
+            BlockDefinition::Trampoline { .. } => None,
+        }
+    }
+}
+
+fn default_hits() -> u32 {
+    1
+}
+
+fn default_launch() -> f32 {
+    900.0
+}
+
+fn default_fall_delay() -> f32 {
+    0.5
+}
+
+/// A box, and how many more hits it takes to break it.
+#[derive(Component)]
+pub struct Breakable {
+    hits: u32,
+}
+
+/// A block that can be bumped from below.
+#[derive(Component)]
+pub struct Bumpable;
+
+/// A bumped block that has let out what it held.
+#[derive(Component)]
+pub struct Emptied;
+
+#[derive(Component)]
+pub struct Trampoline {
+    launch: f32,
+}
+
+/// A platform waiting to be stood on, or counting down to falling.
+#[derive(Component)]
+pub struct FallingPlatform {
+    timer: Timer,
+    triggered: bool,
+}
+
+/// A fruit that's inside the block with this key, and can't be seen or
+/// collected until the block lets it out.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Boxed(pub LevelKey);
+
+/// A block playing its reaction to being hit, after which it goes back to its
+/// `rest` image.
+#[derive(Component)]
+pub struct Reacting {
+    timer: Timer,
+    rest: Handle<Image>,
+}
+
+#[derive(Resource)]
+pub struct BlockTextures {
+    box_idle: Handle<Image>,
+    box_hit: Handle<Image>,
+    box_hit_layout: Handle<TextureAtlasLayout>,
+    block: Handle<Image>,
+    trampoline_idle: Handle<Image>,
+    trampoline_jump: Handle<Image>,
+    trampoline_jump_layout: Handle<TextureAtlasLayout>,
+    falling_on: Handle<Image>,
+    falling_on_layout: Handle<TextureAtlasLayout>,
+    falling_off: Handle<Image>,
+}
+
+/// The size of a bumped block, in pixels.
+const TILE: f32 = 16.0;
+const BOX_SIZE: UVec2 = UVec2::new(28, 24);
+const BOX_HIT_FRAMES: usize = 3;
+const TRAMPOLINE_SIZE: u32 = 28;
+const TRAMPOLINE_JUMP_FRAMES: usize = 8;
+const FALLING_SIZE: UVec2 = UVec2::new(32, 10);
+const FALLING_FRAMES: usize = 4;
+const FRAME_SECONDS: f32 = 0.05;
+
+/// How long a bumped block stays up.
+const BUMP_SECONDS: f32 = 0.15;
+
+/// How fast a character that lands on a box bounces back up.
+const BOX_BOUNCE: f32 = 300.0;
+
+/// An emptied block's color.
+const EMPTIED_COLOR: Color = Color::srgb(0.55, 0.55, 0.6);
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut loading: ResMut<LoadingAssets>,
+) {
+    #[allow(clippy::cast_possible_truncation)]
+    let textures = BlockTextures {
+        box_idle: asset_server.load(asset_path("textures/box_idle.png")),
+        box_hit: asset_server.load(asset_path("textures/box_hit.png")),
+        box_hit_layout: layouts.add(TextureAtlasLayout::from_grid(
+            BOX_SIZE,
+            BOX_HIT_FRAMES as u32,
+            1,
+            None,
+            None,
+        )),
+        block: asset_server.load(asset_path("textures/block.png")),
+        trampoline_idle: asset_server.load(asset_path("textures/trampoline_idle.png")),
+        trampoline_jump: asset_server.load(asset_path("textures/trampoline_jump.png")),
+        trampoline_jump_layout: layouts.add(TextureAtlasLayout::from_grid(
+            UVec2::splat(TRAMPOLINE_SIZE),
+            TRAMPOLINE_JUMP_FRAMES as u32,
+            1,
+            None,
+            None,
+        )),
+        falling_on: asset_server.load(asset_path("textures/falling_platform_on.png")),
+        falling_on_layout: layouts.add(TextureAtlasLayout::from_grid(
+            FALLING_SIZE,
+            FALLING_FRAMES as u32,
+            1,
+            None,
+            None,
+        )),
+        falling_off: asset_server.load(asset_path("textures/falling_platform_off.png")),
+    };
+
+    loading.add(textures.box_idle.clone());
+    loading.add(textures.box_hit.clone());
+    loading.add(textures.block.clone());
+    loading.add(textures.trampoline_idle.clone());
+    loading.add(textures.trampoline_jump.clone());
+    loading.add(textures.falling_on.clone());
+    loading.add(textures.falling_off.clone());
+    commands.insert_resource(textures);
+}
+
+/// Spawn the `index`th block in the level, along with what it holds. Broken
+/// boxes stay broken, and emptied blocks empty, until the level state says
+/// otherwise.
+pub fn spawn_block(
+    commands: &mut Commands,
+    definition: &BlockDefinition,
+    index: usize,
+    state: &LevelState,
+) {
+    let key = LevelKey("block", index);
+    let cleared = state.is_cleared(key);
+    let (drops, position, restart) = match definition {
+        BlockDefinition::Box {
+            position,
+            drops,
+            restart,
+            ..
+        } => (*drops, *position, *restart),
+        BlockDefinition::Bump {
+            position,
+            drops,
+            restart,
+        } => (*drops, *position + Vec2::Y * TILE * PIXEL_SCALE, *restart),
+        BlockDefinition::Falling { restart, .. } => {
+            if !cleared {
+                commands.spawn((definition.clone(), key, *restart, LevelEntity));
+            }
+            return;
+        }
+        BlockDefinition::Trampoline { .. } => {
+            commands.spawn((definition.clone(), LevelEntity));
+            return;
+        }
+    };
+
+    let broken = cleared && matches!(definition, BlockDefinition::Box { .. });
+    if !broken {
+        let mut block = commands.spawn((definition.clone(), key, restart, LevelEntity));
+        if cleared {
+            block.insert(Emptied);
+        }
+    }
+
+    if let Some(kind) = drops {
+        let fruit_key = LevelKey("block fruit", index);
+        let fruit = fruit::spawn_fruit(
+            commands,
+            &FruitDefinition {
+                position,
+                kind,
+                restart,
+            },
+            fruit_key,
+            state.is_cleared(fruit_key),
+        );
+        if !cleared {
+            commands.entity(fruit).insert(Boxed(key));
+        }
+    }
+}
+
+/// Give blocks spawned by the level their sprites and bodies.
+pub fn build_blocks(
+    mut commands: Commands,
+    blocks: Query<(Entity, &BlockDefinition, Has<Emptied>), Added<BlockDefinition>>,
+    textures: Res<BlockTextures>,
+) {
+    for (entity, definition, emptied) in &blocks {
+        let mut block = commands.entity(entity);
+        match definition {
+            BlockDefinition::Box { position, hits, .. } => {
+                let sprite = Sprite::from_image(textures.box_idle.clone());
+                block.insert((
+                    solid(sprite, *position, 1, Vec2::new(24.0, 22.0), Vec2::ZERO),
+                    Breakable { hits: *hits },
+                ));
+            }
+            BlockDefinition::Bump { position, .. } => {
+                let mut sprite = Sprite::from_image(textures.block.clone());
+                if emptied {
+                    sprite.color = EMPTIED_COLOR;
+                }
+                block.insert((
+                    solid(sprite, *position, 1, Vec2::splat(TILE), Vec2::ZERO),
+                    Bumpable,
+                ));
+            }
+            BlockDefinition::Trampoline { position, launch } => {
+                #[allow(clippy::cast_precision_loss)]
+                let center = *position + Vec2::Y * TRAMPOLINE_SIZE as f32 / 2.0 * PIXEL_SCALE;
+                let sprite = Sprite::from_image(textures.trampoline_idle.clone());
+                // Only the pad at the bottom is solid.
+                block.insert((
+                    solid(
+                        sprite,
+                        center,
+                        1,
+                        Vec2::new(22.0, 10.0),
+                        Vec2::new(0.0, -9.0),
+                    ),
+                    Trampoline { launch: *launch },
+                ));
+            }
+            BlockDefinition::Falling {
+                position, delay, ..
+            } => {
+                let sprite = Sprite::from_atlas_image(
+                    textures.falling_on.clone(),
+                    TextureAtlas {
+                        layout: textures.falling_on_layout.clone(),
+                        index: 0,
+                    },
+                );
+                block.insert((
+                    solid(
+                        sprite,
+                        *position,
+                        FALLING_FRAMES,
+                        Vec2::new(32.0, 6.0),
+                        Vec2::new(0.0, 2.0),
+                    ),
+                    FallingPlatform {
+                        timer: Timer::from_seconds(*delay, TimerMode::Once),
+                        triggered: false,
+                    },
+                    PhysicsPosition::new(*position),
+                    Velocity {
+                        x: 0.0,
+                        y: 0.0,
+                        is_grounded: true,
+                    },
+                ));
+            }
+        }
+    }
+}
+
+/// A block's sprite, and a solid hitbox of `size` that's `offset` from the
+/// middle of the sprite, both in the image's pixels.
+fn solid(sprite: Sprite, position: Vec2, frames: usize, size: Vec2, offset: Vec2) -> impl Bundle {
+    (
+        sprite,
+        Transform {
+            translation: position.extend(4.0),
+            scale: Vec3::splat(PIXEL_SCALE),
+            ..default()
+        },
+        LoopingAnimation::new(frames, FRAME_SECONDS),
+        Collider {
+            size: size * PIXEL_SCALE,
+            offset: offset * PIXEL_SCALE,
+        },
+        Platform,
+    )
+}
+
+/// What blocks hold, and what the level remembers about letting it out.
+#[derive(SystemParam)]
+pub struct Contents<'w, 's> {
+    fruits: Query<'w, 's, (Entity, &'static Boxed, &'static mut Visibility)>,
+    state: ResMut<'w, LevelState>,
+}
+
+impl Contents<'_, '_> {
+    /// Let out the fruit held by the block with this key, if there is one.
+    fn release(&mut self, commands: &mut Commands, key: LevelKey) -> bool {
+        let Some((fruit, _, mut visibility)) =
+            self.fruits.iter_mut().find(|(_, boxed, _)| boxed.0 == key)
+        else {
+            return false;
+        };
+        commands.entity(fruit).remove::<Boxed>();
+        *visibility = Visibility::Inherited;
+        true
+    }
+}
+
+/// Hit boxes that characters jump into from below or land on, breaking them
+/// once they've taken enough hits.
+pub fn break_boxes(
+    mut commands: Commands,
+    mut collided: EventReader<Collided>,
+    mut characters: Query<&mut Velocity, With<Character>>,
+    mut boxes: Query<(
+        &mut Breakable,
+        &LevelKey,
+        &RestartPolicy,
+        &mut Sprite,
+        &mut LoopingAnimation,
+    )>,
+    mut contents: Contents,
+    textures: Res<BlockTextures>,
+) {
+    for collision in collided.read() {
+        let hit = match collision.side {
+            Collision::Bottom => collision.velocity.y > 0.0,
+            Collision::Top => collision.velocity.y < 0.0,
+            Collision::Left | Collision::Right => false,
+        };
+        if !hit {
+            continue;
+        }
+        let Ok(mut velocity) = characters.get_mut(collision.entity) else {
+            continue;
+        };
+        let Ok((mut breakable, key, policy, mut sprite, mut animation)) =
+            boxes.get_mut(collision.other)
+        else {
+            continue;
+        };
+        if breakable.hits == 0 {
+            continue;
+        }
+
+        if collision.side == Collision::Top {
+            velocity.y = BOX_BOUNCE;
+            velocity.is_grounded = false;
+        }
+
+        breakable.hits -= 1;
+        if breakable.hits == 0 {
+            commands.entity(collision.other).despawn_recursive();
+            contents.release(&mut commands, *key);
+            contents.state.clear(*key, *policy);
+            continue;
+        }
+
+        react(
+            &mut commands,
+            collision.other,
+            &mut sprite,
+            &mut animation,
+            (&textures.box_hit, &textures.box_hit_layout, BOX_HIT_FRAMES),
+            &textures.box_idle,
+        );
+    }
+}
+
+/// Bump blocks that characters jump into from below, knocking out enemies
+/// standing on them and letting out what they hold.
+pub fn bump_blocks(
+    mut commands: Commands,
+    mut collided: EventReader<Collided>,
+    characters: Query<(), With<Character>>,
+    enemies: Query<(), (With<Enemy>, Without<Defeated>)>,
+    mut blocks: Query<
+        (&LevelKey, &RestartPolicy, &mut Sprite),
+        (With<Bumpable>, Without<Reacting>),
+    >,
+    mut contents: Contents,
+    textures: Res<BlockTextures>,
+) {
+    let collisions: Vec<Collided> = collided.read().copied().collect();
+    for bump in &collisions {
+        if bump.side != Collision::Bottom
+            || bump.velocity.y <= 0.0
+            || !characters.contains(bump.entity)
+        {
+            continue;
+        }
+        let Ok((key, policy, mut sprite)) = blocks.get_mut(bump.other) else {
+            continue;
+        };
+
+        // It jumps up a little, without its collider going anywhere.
+        sprite.anchor = Anchor::Custom(Vec2::new(0.0, -0.25));
+        commands.entity(bump.other).insert(Reacting {
+            timer: Timer::from_seconds(BUMP_SECONDS, TimerMode::Once),
+            rest: textures.block.clone(),
+        });
+
+        for standing in &collisions {
+            if standing.other == bump.other
+                && standing.side == Collision::Top
+                && enemies.contains(standing.entity)
+            {
+                commands.entity(standing.entity).insert(Defeated);
+            }
+        }
+
+        if contents.release(&mut commands, *key) {
+            sprite.color = EMPTIED_COLOR;
+            commands.entity(bump.other).insert(Emptied);
+            contents.state.clear(*key, *policy);
+        }
+    }
+}
+
+/// Send whatever lands on a trampoline flying.
+pub fn bounce_on_trampolines(
+    mut commands: Commands,
+    mut collided: EventReader<Collided>,
+    mut bodies: Query<&mut Velocity>,
+    mut trampolines: Query<(&Trampoline, &mut Sprite, &mut LoopingAnimation)>,
+    textures: Res<BlockTextures>,
+) {
+    for collision in collided.read() {
+        if collision.side != Collision::Top {
+            continue;
+        }
+        let Ok((trampoline, mut sprite, mut animation)) = trampolines.get_mut(collision.other)
+        else {
+            continue;
+        };
+        let Ok(mut velocity) = bodies.get_mut(collision.entity) else {
+            continue;
+        };
+
+        velocity.y = trampoline.launch;
+        velocity.is_grounded = false;
+        react(
+            &mut commands,
+            collision.other,
+            &mut sprite,
+            &mut animation,
+            (
+                &textures.trampoline_jump,
+                &textures.trampoline_jump_layout,
+                TRAMPOLINE_JUMP_FRAMES,
+            ),
+            &textures.trampoline_idle,
+        );
+    }
+}
+
+/// Play a block's reaction from the start.
+fn react(
+    commands: &mut Commands,
+    block: Entity,
+    sprite: &mut Sprite,
+    animation: &mut LoopingAnimation,
+    (image, layout, frames): (&Handle<Image>, &Handle<TextureAtlasLayout>, usize),
+    rest: &Handle<Image>,
+) {
+    sprite.image = image.clone();
+    sprite.texture_atlas = Some(TextureAtlas {
+        layout: layout.clone(),
+        index: 0,
+    });
+    animation.frames = frames;
+    animation.timer.reset();
+
+    #[allow(clippy::cast_precision_loss)]
+    let seconds = frames as f32 * FRAME_SECONDS;
+    commands.entity(block).insert(Reacting {
+        timer: Timer::from_seconds(seconds, TimerMode::Once),
+        rest: rest.clone(),
+    });
+}
+
+/// Put blocks back how they were once they've finished reacting.
+pub fn settle_blocks(
+    mut commands: Commands,
+    mut blocks: Query<(Entity, &mut Reacting, &mut Sprite, &mut LoopingAnimation)>,
+    time: Res<Time>,
+) {
+    for (entity, mut reacting, mut sprite, mut animation) in &mut blocks {
+        if !reacting.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        sprite.image = reacting.rest.clone();
+        sprite.texture_atlas = None;
+        sprite.anchor = Anchor::Center;
+        animation.frames = 1;
+        commands.entity(entity).remove::<Reacting>();
+    }
+}
+
+/// Start the countdown on falling platforms that characters stand on.
+pub fn trigger_falling_platforms(
+    mut collided: EventReader<Collided>,
+    characters: Query<(), With<Character>>,
+    mut platforms: Query<(&mut FallingPlatform, &mut Sprite)>,
+    textures: Res<BlockTextures>,
+) {
+    for collision in collided.read() {
+        if collision.side != Collision::Top || !characters.contains(collision.entity) {
+            continue;
+        }
+        let Ok((mut platform, mut sprite)) = platforms.get_mut(collision.other) else {
+            continue;
+        };
+        if platform.triggered {
+            continue;
+        }
+
+        // Its propeller stops.
+        platform.triggered = true;
+        sprite.image = textures.falling_off.clone();
+        sprite.texture_atlas = None;
+    }
+}
+
+/// Drop falling platforms whose countdown has run out, and get rid of them
+/// once they've fallen below the [`KillHeight`].
+///
+/// A falling platform stays solid, so anybody who doesn't jump off in time
+/// rides it down. It doesn't collide with anything itself, so it falls
+/// through the terrain and out of the level. It's cleared in the
+/// [`LevelState`] as soon as it drops, so that it comes back when the level
+/// is reset, as its [`RestartPolicy`] says.
+pub fn drop_falling_platforms(
+    mut commands: Commands,
+    mut platforms: Query<(
+        Entity,
+        &mut FallingPlatform,
+        &mut Velocity,
+        &PhysicsPosition,
+        &LevelKey,
+        &RestartPolicy,
+    )>,
+    kill_height: Option<Res<KillHeight>>,
+    mut state: ResMut<LevelState>,
+    time: Res<Time>,
+) {
+    for (entity, mut platform, mut velocity, position, key, policy) in &mut platforms {
+        if kill_height
+            .as_ref()
+            .is_some_and(|height| position.current.y < height.0)
+        {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        if !platform.triggered || !platform.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        velocity.is_grounded = false;
+        state.clear(*key, *policy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A block spawned and built the way a level would, and a character to
+    /// run into it, in an app running the blocks' reactions.
+    struct Scene {
+        app: App,
+        block: Entity,
+        fruit: Option<Entity>,
+        character: Entity,
+    }
+
+    impl Scene {
+        fn new(definition: &BlockDefinition) -> Self {
+            let mut app = App::new();
+            app.add_plugins(MinimalPlugins)
+                .insert_resource(BlockTextures {
+                    box_idle: Handle::default(),
+                    box_hit: Handle::default(),
+                    box_hit_layout: Handle::default(),
+                    block: Handle::default(),
+                    trampoline_idle: Handle::default(),
+                    trampoline_jump: Handle::default(),
+                    trampoline_jump_layout: Handle::default(),
+                    falling_on: Handle::default(),
+                    falling_on_layout: Handle::default(),
+                    falling_off: Handle::default(),
+                })
+                .init_resource::<LevelState>()
+                .add_event::<Collided>()
+                .add_systems(
+                    Update,
+                    (
+                        build_blocks,
+                        (break_boxes, bump_blocks, bounce_on_trampolines),
+                    )
+                        .chain(),
+                );
+
+            let world = app.world_mut();
+            spawn_block(&mut world.commands(), definition, 0, &LevelState::default());
+            world.flush();
+            let block = world
+                .query_filtered::<Entity, With<BlockDefinition>>()
+                .single(world);
+            // Fruit gets its visibility when it's built, which is left out here.
+            let fruit = world
+                .query_filtered::<Entity, With<Boxed>>()
+                .get_single(world)
+                .ok();
+            if let Some(fruit) = fruit {
+                world.entity_mut(fruit).insert(Visibility::Hidden);
+            }
+            let character = world
+                .spawn((
+                    Character,
+                    Velocity {
+                        x: 0.0,
+                        y: 0.0,
+                        is_grounded: false,
+                    },
+                ))
+                .id();
+
+            app.update();
+            Scene {
+                app,
+                block,
+                fruit,
+                character,
+            }
+        }
+
+        /// Run the character into the block, and let the block react.
+        fn collide(&mut self, side: Collision, velocity: Vec2) {
+            self.app.world_mut().send_event(Collided {
+                entity: self.character,
+                other: self.block,
+                side,
+                velocity,
+            });
+            self.app.update();
+        }
+
+        fn get<C: Component>(&self, entity: Entity) -> Option<&C> {
+            self.app.world().get::<C>(entity)
+        }
+    }
+
+    const HEAD_BUTT: Vec2 = Vec2::new(0.0, 400.0);
+
+    #[test]
+    fn boxes_break_after_their_hits_and_let_out_their_fruit() {
+        let mut scene = Scene::new(&BlockDefinition::Box {
+            position: Vec2::ZERO,
+            hits: 2,
+            drops: Some(FruitKind::Cherries),
+            restart: RestartPolicy::Reset,
+        });
+        let fruit = scene.fruit.unwrap();
+
+        scene.collide(Collision::Bottom, HEAD_BUTT);
+        assert!(scene.app.world().get_entity(scene.block).is_ok());
+        assert!(scene.get::<Boxed>(fruit).is_some());
+
+        // Walking into its side doesn't count.
+        scene.collide(Collision::Left, HEAD_BUTT);
+        assert!(scene.app.world().get_entity(scene.block).is_ok());
+
+        scene.collide(Collision::Bottom, HEAD_BUTT);
+        assert!(scene.app.world().get_entity(scene.block).is_err());
+        assert!(scene.get::<Boxed>(fruit).is_none());
+        assert_eq!(scene.get::<Visibility>(fruit), Some(&Visibility::Inherited));
+        assert!(scene
+            .app
+            .world()
+            .resource::<LevelState>()
+            .is_cleared(LevelKey("block", 0)));
+    }
+
+    #[test]
+    fn bumps_knock_out_enemies_on_top_and_let_out_the_fruit_once() {
+        let mut scene = Scene::new(&BlockDefinition::Bump {
+            position: Vec2::ZERO,
+            drops: Some(FruitKind::Apple),
+            restart: RestartPolicy::Reset,
+        });
+        let fruit = scene.fruit.unwrap();
+        let enemy = scene
+            .app
+            .world_mut()
+            .spawn(Enemy {
+                kind: String::new(),
+                facing: 1.0,
+            })
+            .id();
+
+        scene.app.world_mut().send_event(Collided {
+            entity: enemy,
+            other: scene.block,
+            side: Collision::Top,
+            velocity: Vec2::ZERO,
+        });
+        scene.collide(Collision::Bottom, HEAD_BUTT);
+        assert!(scene.get::<Defeated>(enemy).is_some());
+        assert!(scene.get::<Boxed>(fruit).is_none());
+        assert_eq!(scene.get::<Visibility>(fruit), Some(&Visibility::Inherited));
+        assert!(scene.get::<Emptied>(scene.block).is_some());
+
+        // Once the fruit is gone, bumping the block again doesn't bring it
+        // back.
+        let world = scene.app.world_mut();
+        *world.get_mut::<Visibility>(fruit).unwrap() = Visibility::Hidden;
+        world.entity_mut(scene.block).remove::<Reacting>();
+        scene.collide(Collision::Bottom, HEAD_BUTT);
+        assert!(scene.get::<Reacting>(scene.block).is_some());
+        assert_eq!(scene.get::<Visibility>(fruit), Some(&Visibility::Hidden));
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn trampolines_launch_whatever_lands_on_them() {
+        let launch = 1200.0;
+        let mut scene = Scene::new(&BlockDefinition::Trampoline {
+            position: Vec2::ZERO,
+            launch,
+        });
+        let landing = Vec2::new(0.0, -300.0);
+
+        // Walking into its side doesn't count.
+        scene.collide(Collision::Left, landing);
+        assert_eq!(scene.get::<Velocity>(scene.character).unwrap().y, 0.0);
+
+        scene.collide(Collision::Top, landing);
+        let velocity = scene.get::<Velocity>(scene.character).unwrap();
+        assert_eq!(velocity.y, launch);
+        assert!(!velocity.is_grounded);
+        assert!(scene.get::<Reacting>(scene.block).is_some());
+    }
+}
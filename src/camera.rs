@@ -1,43 +1,252 @@
-use bevy::prelude::*;
+use bevy::{
+    ecs::system::SystemParam, math::StableInterpolate, prelude::*, utils::HashSet,
+    window::PrimaryWindow,
+};
 
-use crate::character::Character;
+use crate::{
+    camera_effects::CameraShake,
+    camera_zones::{CameraZone, ZoneBehavior, ZoneState},
+    character::Character,
+    level::LevelBounds,
+    physics::Velocity,
+    pixel_perfect::RenderMode,
+    restart::ResetLevel,
+};
 
 #[derive(Component)]
 pub struct CharacterCamera;
 
+/// Where the camera is looking and how far it's zoomed out, as decided by
+/// tracking the players.
+///
+/// The camera's `Transform` and projection are set from this every frame, with
+/// effects such as screen shake layered on top, so that those effects never
+/// feed back into the tracking.
+#[derive(Component)]
+pub struct CameraView {
+    pub translation: Vec2,
+    pub scale: f32,
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        CameraView {
+            translation: Vec2::ZERO,
+            scale: 1.0,
+        }
+    }
+}
+
 pub fn setup(mut commands: Commands) {
-    commands.spawn((Camera2d, CharacterCamera));
+    commands.spawn((
+        Camera2d,
+        CharacterCamera,
+        CameraView::default(),
+        CameraShake::default(),
+        CameraFocus::default(),
+        ZoneState::default(),
+    ));
 }
 
 /// On restart, reset the camera right away rather than slowly
-/// lerping it back to the character.
-pub fn reset(mut camera_transform: Query<&mut Transform, With<CharacterCamera>>) {
-    let Ok(mut camera_transform) = camera_transform.get_single_mut() else {
+/// easing it back to the character.
+pub fn reset(
+    mut resets: EventReader<ResetLevel>,
+    mut camera: Query<(&mut CameraView, &mut CameraFocus, &mut ZoneState), With<CharacterCamera>>,
+) {
+    if !resets.read().any(|ResetLevel(cause)| cause.is_restart()) {
+        return;
+    }
+    let Ok((mut view, mut focus, mut zone_state)) = camera.get_single_mut() else {
         return;
     };
 
-    camera_transform.translation = Vec3::new(0.0, 0.0, 0.0);
+    *view = CameraView::default();
+    *focus = CameraFocus::default();
+    *zone_state = ZoneState::default();
+}
+
+/// How much room to leave around the outermost players, in world units.
+const FRAMING_MARGIN: f32 = 150.0;
+
+/// How far the camera is allowed to zoom out to keep everybody on screen.
+const MAX_ZOOM_OUT: f32 = 2.0;
+
+/// How the camera follows the players.
+#[derive(Resource)]
+pub struct CameraSettings {
+    /// How quickly the camera closes the distance to where it wants to be.
+    /// After one second, `exp(-decay_rate)` of the distance is left.
+    pub decay_rate: f32,
+    /// Half the size of the box the players can move around in without the
+    /// camera following them.
+    ///
+    /// Sideways, the camera follows as soon as they push past it. Vertically,
+    /// it only follows them down past it, so that it keeps up with a fall.
+    /// Going up, it waits for somebody to land, unless they'd otherwise leave
+    /// the top of the screen.
+    pub deadzone: Vec2,
+    /// How far ahead of the players to look, in the direction they face.
+    pub lookahead: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            decay_rate: 4.0,
+            deadzone: Vec2::new(40.0, 60.0),
+            lookahead: 80.0,
+        }
+    }
+}
+
+/// The point the camera is following, before lookahead is added.
+///
+/// This only moves when the players push against the edge of the deadzone,
+/// or vertically when somebody lands, so that the camera doesn't bob up and
+/// down with every jump. See [`CameraSettings::deadzone`] for the details.
+#[derive(Component, Default)]
+pub struct CameraFocus {
+    pub point: Vec2,
+    /// The characters that were standing on something as of the last frame,
+    /// to tell when somebody lands.
+    grounded: HashSet<Entity>,
+}
+
+/// How much of the world the camera can show, and where it has to stay.
+#[derive(SystemParam)]
+pub struct Viewport<'w, 's> {
+    window: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    render_mode: Res<'w, RenderMode>,
+    bounds: Option<Res<'w, LevelBounds>>,
 }
 
-/// Track the character with the camera.
+impl Viewport<'_, '_> {
+    /// The size of the view at a scale of 1, if there's a window.
+    fn size(&self) -> Option<Vec2> {
+        let window = self.window.get_single().ok()?;
+        Some(self.render_mode.view_size(window))
+    }
+}
+
+/// Track the characters with the camera.
 ///
-/// Make the tracking smooth by applying linear interpolation (lerp).
+/// The camera aims for the middle of all the players and zooms out when they
+/// spread apart so that nobody walks off screen. Make the tracking smooth by
+/// decaying the distance to the target exponentially, which (unlike a lerp
+/// by `delta * speed`) ends up in the same place at any frame rate.
 pub fn track_character(
-    mut camera_transform: Query<&mut Transform, With<CharacterCamera>>,
-    character_transform: Query<&Transform, (With<Character>, Without<CharacterCamera>)>,
+    mut camera: Query<(&mut CameraView, &mut CameraFocus, &ZoneState), With<CharacterCamera>>,
+    zones: Query<&CameraZone>,
+    characters: Query<(Entity, &Transform, &Sprite, &Velocity), With<Character>>,
+    viewport: Viewport,
+    settings: Res<CameraSettings>,
     time: Res<Time>,
 ) {
-    let Ok(mut camera_transform) = camera_transform.get_single_mut() else {
+    let Ok((mut view, mut focus, zone_state)) = camera.get_single_mut() else {
+        return;
+    };
+    let Some(view_size) = viewport.size() else {
         return;
     };
 
-    let Ok(character_transform) = character_transform.get_single() else {
+    let mut positions = characters
+        .iter()
+        .map(|(_, transform, ..)| transform.translation.truncate());
+    let Some(first) = positions.next() else {
         return;
     };
+    let (min, max) = positions.fold((first, first), |(min, max), position| {
+        (min.min(position), max.max(position))
+    });
+    let center = (min + max) / 2.0;
+
+    // Only drag the focus along when the players reach the edge of the
+    // deadzone.
+    let offset = center - focus.point;
+    let outside = offset.abs() - settings.deadzone;
+    if outside.x > 0.0 {
+        focus.point.x += outside.x.copysign(offset.x);
+    }
+    let grounded: HashSet<Entity> = characters
+        .iter()
+        .filter(|(.., velocity)| velocity.is_grounded)
+        .map(|(entity, ..)| entity)
+        .collect();
+    let landed = grounded.difference(&focus.grounded).next().is_some();
+    focus.grounded = grounded;
+    // Above, the edge of the screen is as far as the players can get before
+    // the camera has to follow them up, landed or not.
+    let above = offset.y - (view_size.y * view.scale / 2.0 - FRAMING_MARGIN);
+    if landed {
+        focus.point.y = center.y;
+    } else if offset.y < 0.0 && outside.y > 0.0 {
+        focus.point.y -= outside.y;
+    } else if above > 0.0 {
+        focus.point.y += above;
+    }
+
+    // Look ahead in the direction everybody is facing, or not at all if
+    // they're facing opposite ways.
+    #[allow(clippy::cast_precision_loss)]
+    let facing = characters
+        .iter()
+        .map(|(_, _, sprite, _)| if sprite.flip_x { -1.0 } else { 1.0 })
+        .sum::<f32>()
+        / characters.iter().len() as f32;
+
+    let zone = zone_state.behavior(&zones);
+
+    let zoom = match zone {
+        Some(ZoneBehavior::Zoom(zoom)) => zoom,
+        _ => 1.0,
+    };
+    let wanted = (max - min + FRAMING_MARGIN * 2.0) / view_size;
+    let scale = wanted.max_element().clamp(zoom, MAX_ZOOM_OUT.max(zoom));
+
+    let mut target = Vec2::new(focus.point.x + facing * settings.lookahead, focus.point.y);
+    match zone {
+        Some(ZoneBehavior::FixY(y)) => target.y = y,
+        Some(ZoneBehavior::LockToRoom) => {
+            if let Some(zone) = zone_state.zone.and_then(|zone| zones.get(zone).ok()) {
+                target = clamp_to_bounds(target, view_size * scale / 2.0, zone.area);
+            }
+        }
+        _ => {}
+    }
+    if let Some((point, _)) = zone_state.pan {
+        target = point;
+    }
+
+    view.translation
+        .smooth_nudge(&target, settings.decay_rate, time.delta_secs());
+    view.scale
+        .smooth_nudge(&scale, settings.decay_rate, time.delta_secs());
+
+    // Unlike zones, which are eased into, the level bounds are a hard limit.
+    if let Some(bounds) = &viewport.bounds {
+        let view_half_size = view_size * view.scale / 2.0;
+        view.translation = clamp_to_bounds(view.translation, view_half_size, bounds.0);
+    }
+}
+
+/// Keep a view of the given half size centered near `center` without it
+/// showing anything outside of `bounds`. On any axis where the view is bigger
+/// than the bounds, center the bounds in the view instead.
+pub fn clamp_to_bounds(center: Vec2, view_half_size: Vec2, bounds: Rect) -> Vec2 {
+    let min = bounds.min + view_half_size;
+    let max = bounds.max - view_half_size;
+    let clamp_axis = |value: f32, min: f32, max: f32, middle: f32| {
+        if min > max {
+            middle
+        } else {
+            value.clamp(min, max)
+        }
+    };
 
-    let Vec3 { x, y, .. } = character_transform.translation;
-    let direction = Vec3::new(x, y, camera_transform.translation.z);
-    camera_transform.translation = camera_transform
-        .translation
-        .lerp(direction, time.delta_secs() * 2.0);
+    let middle = bounds.center();
+    Vec2::new(
+        clamp_axis(center.x, min.x, max.x, middle.x),
+        clamp_axis(center.y, min.y, max.y, middle.y),
+    )
 }
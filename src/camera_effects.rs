@@ -0,0 +1,114 @@
+//! Screen shake and other camera effects.
+//!
+//! Gameplay sends a [`CameraEffect`] and doesn't need to know anything about
+//! the camera. The effects are layered on top of the [`CameraView`] when the
+//! camera's `Transform` is set, so they never throw off the tracking.
+
+use bevy::prelude::*;
+
+use crate::camera::{CameraView, CharacterCamera};
+
+/// How much trauma wears off per second. Trauma is between 0.0 and 1.0.
+const TRAUMA_DECAY: f32 = 1.2;
+
+/// How quickly a zoom punch eases back out, as an exponential decay rate.
+const PUNCH_DECAY: f32 = 8.0;
+
+/// How far the camera moves at full trauma, in world units.
+const MAX_SHAKE_OFFSET: f32 = 16.0;
+
+/// How far the camera tilts at full trauma, in radians.
+const MAX_SHAKE_ANGLE: f32 = 0.05;
+
+/// How quickly the shake wobbles, in noise samples per second.
+const SHAKE_FREQUENCY: f32 = 20.0;
+
+#[derive(Event, Debug, Clone, Copy)]
+pub enum CameraEffect {
+    /// Add trauma, between 0.0 and 1.0. Trauma adds up, so several small
+    /// knocks in a row shake as hard as one big one.
+    Shake(f32),
+    /// Zoom in by this fraction of the view (e.g. 0.1 for 10%) and ease back.
+    ZoomPunch(f32),
+}
+
+/// The effects currently playing on a camera.
+#[derive(Component, Default)]
+pub struct CameraShake {
+    trauma: f32,
+    punch: f32,
+}
+
+pub fn receive_camera_effects(
+    mut events: EventReader<CameraEffect>,
+    mut camera: Query<&mut CameraShake, With<CharacterCamera>>,
+) {
+    let Ok(mut shake) = camera.get_single_mut() else {
+        events.clear();
+        return;
+    };
+
+    for event in events.read() {
+        match *event {
+            CameraEffect::Shake(trauma) => {
+                shake.trauma = (shake.trauma + trauma).clamp(0.0, 1.0);
+            }
+            CameraEffect::ZoomPunch(amount) => {
+                shake.punch = shake.punch.max(amount);
+            }
+        }
+    }
+}
+
+/// Put the camera where the [`CameraView`] says, plus any shake, and wear the
+/// effects off.
+pub fn apply_camera_effects(
+    mut camera: Query<
+        (
+            &CameraView,
+            &mut CameraShake,
+            &mut Transform,
+            &mut OrthographicProjection,
+        ),
+        With<CharacterCamera>,
+    >,
+    time: Res<Time>,
+) {
+    let Ok((view, mut shake, mut transform, mut projection)) = camera.get_single_mut() else {
+        return;
+    };
+
+    // Squaring trauma makes small knocks subtle and big ones violent.
+    let strength = shake.trauma * shake.trauma;
+    let t = time.elapsed_secs() * SHAKE_FREQUENCY;
+    let offset = Vec2::new(noise(0, t), noise(1, t)) * MAX_SHAKE_OFFSET * strength;
+    let angle = noise(2, t) * MAX_SHAKE_ANGLE * strength;
+
+    transform.translation.x = view.translation.x + offset.x;
+    transform.translation.y = view.translation.y + offset.y;
+    transform.rotation = Quat::from_rotation_z(angle);
+    projection.scale = view.scale * (1.0 - shake.punch);
+
+    shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_secs()).max(0.0);
+    shake.punch *= (-PUNCH_DECAY * time.delta_secs()).exp();
+}
+
+/// Smooth noise between -1.0 and 1.0. Each `seed` gives a different,
+/// unrelated wobble, so that the axes don't shake in lockstep.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn noise(seed: u32, t: f32) -> f32 {
+    let lattice = |i: u32| {
+        // A cheap integer hash, mapped to -1.0..=1.0.
+        let mut x = i.wrapping_mul(0x9E37_79B9) ^ seed.wrapping_mul(0x85EB_CA6B);
+        x ^= x >> 16;
+        x = x.wrapping_mul(0x7FEB_352D);
+        x ^= x >> 15;
+        f32::from(x as u16) / f32::from(u16::MAX) * 2.0 - 1.0
+    };
+
+    let floor = t.floor();
+    let i = floor as u32;
+    let fraction = t - floor;
+    let smoothed = fraction * fraction * (3.0 - 2.0 * fraction);
+    lattice(i).lerp(lattice(i.wrapping_add(1)), smoothed)
+}
@@ -0,0 +1,108 @@
+//! Areas of a level that change how the camera behaves while the players are
+//! inside them.
+//!
+//! Zones don't move the camera themselves. They decide what the camera should
+//! be aiming for, and `camera::track_character` eases it there, so going in
+//! and out of a zone blends rather than cuts.
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    camera::{CameraFocus, CharacterCamera},
+    level::{LevelEntity, LevelKey, LevelState},
+    restart::RestartPolicy,
+};
+
+/// An area of the level with its own camera behavior.
+#[derive(Component, Debug, Clone, Deserialize)]
+pub struct CameraZone {
+    /// Where the zone is, in world coordinates.
+    pub area: Rect,
+    /// When zones overlap, the one with the highest priority wins. Overlapping
+    /// zones should not share a priority, or which one wins is arbitrary.
+    pub priority: i32,
+    pub behavior: ZoneBehavior,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ZoneBehavior {
+    /// Keep the camera inside the zone, like a room.
+    LockToRoom,
+    /// Keep the camera at this height.
+    FixY(f32),
+    /// Use this zoom level (larger shows more) instead of the default.
+    Zoom(f32),
+    /// Show the players something for a while before returning control to
+    /// them. This only happens the first time the zone is entered, even if
+    /// the level is restarted from a checkpoint, and after that the zone is
+    /// as good as gone, so zones under it take over.
+    PanTo { point: Vec2, seconds: f32 },
+}
+
+/// Marks a [`ZoneBehavior::PanTo`] zone that has already been shown.
+#[derive(Component)]
+pub struct Panned;
+
+pub fn spawn_camera_zone(commands: &mut Commands, zone: &CameraZone, key: LevelKey, panned: bool) {
+    let mut zone = commands.spawn((zone.clone(), key, RestartPolicy::Persist, LevelEntity));
+    if panned {
+        zone.insert(Panned);
+    }
+}
+
+/// Which zone the camera is in, and whether it's in the middle of a pan.
+#[derive(Component, Default)]
+pub struct ZoneState {
+    pub zone: Option<Entity>,
+    pub pan: Option<(Vec2, Timer)>,
+}
+
+impl ZoneState {
+    /// What the current zone wants done, if anything.
+    pub fn behavior(&self, zones: &Query<&CameraZone>) -> Option<ZoneBehavior> {
+        self.zone
+            .and_then(|zone| zones.get(zone).ok())
+            .map(|zone| zone.behavior)
+    }
+}
+
+/// Work out which zone the camera's focus is in, and start or finish pans.
+pub fn update_camera_zones(
+    mut commands: Commands,
+    mut camera: Query<(&CameraFocus, &mut ZoneState), With<CharacterCamera>>,
+    zones: Query<(Entity, &CameraZone, &LevelKey, &RestartPolicy, Has<Panned>)>,
+    mut level: ResMut<LevelState>,
+    time: Res<Time>,
+) {
+    let Ok((focus, mut state)) = camera.get_single_mut() else {
+        return;
+    };
+
+    if let Some((_, timer)) = &mut state.pan {
+        if timer.tick(time.delta()).finished() {
+            state.pan = None;
+        }
+    }
+
+    let current = zones
+        .iter()
+        .filter(|(_, zone, .., panned)| !panned && zone.area.contains(focus.point))
+        .max_by_key(|(_, zone, ..)| zone.priority);
+
+    let Some((entity, zone, key, policy, _)) = current else {
+        state.zone = None;
+        return;
+    };
+
+    if state.zone == Some(entity) {
+        return;
+    }
+    state.zone = Some(entity);
+
+    if let ZoneBehavior::PanTo { point, seconds } = zone.behavior {
+        state.pan = Some((point, Timer::from_seconds(seconds, TimerMode::Once)));
+        commands.entity(entity).insert(Panned);
+        level.clear(*key, *policy);
+    }
+}
@@ -1,77 +1,274 @@
-use bevy::prelude::*;
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
 
 use crate::{
-    animation::{AnimationIndices, AnimationTimer},
-    physics::{Collider, Grounded, Velocity},
-    restart::RestartRespawn,
+    animation::{AnimationIndices, AnimationTimer, CharacterAnimationDefinition},
+    assets::asset_path,
+    camera_effects::CameraEffect,
+    checkpoint::RespawnPoint,
+    health::{Health, Hurt, MAX_HEALTH},
+    input::{Player, Roster},
+    loading::LoadingAssets,
+    physics::{Collider, CollisionLayer, Grounded, Layer, PhysicsPosition, Velocity},
+    pixel_perfect::PIXEL_SCALE,
+    restart::ResetLevel,
+    tuning::MovementTuning,
 };
 
 #[derive(Component)]
 pub struct Character;
 
+/// Every player's character's animations, by player.
 #[derive(Resource)]
-pub struct CharacterAnimations {
+pub struct CharacterAnimations(Vec<CharacterSkin>);
+
+impl CharacterAnimations {
+    pub fn for_player(&self, player: Player) -> &CharacterSkin {
+        &self.0[player.0 % self.0.len()]
+    }
+}
+
+/// One character's animations.
+pub struct CharacterSkin {
     pub idle_texture: Handle<Image>,
     pub idle_layout: Handle<TextureAtlasLayout>,
     pub run_texture: Handle<Image>,
     pub run_layout: Handle<TextureAtlasLayout>,
+    pub hit_texture: Handle<Image>,
+    pub hit_layout: Handle<TextureAtlasLayout>,
     pub jump_texture: Handle<Image>,
+    pub indices: AnimationIndices,
+    pub frame_seconds: f32,
+    pub color: Color,
 }
 
+/// The definitions [`CharacterAnimations`] is built from, by player.
+#[derive(Resource)]
+pub struct CharacterAnimationsHandle(Vec<Handle<CharacterAnimationDefinition>>);
+
+/// Each player's character, in the order players join.
+const SKINS: [&str; 4] = [
+    "animations/ninja_frog.anim.ron",
+    "animations/mask_dude.anim.ron",
+    "animations/pink_man.anim.ron",
+    "animations/virtual_guy.anim.ron",
+];
+
 #[derive(Component, PartialEq)]
 pub enum CharacterState {
     Idle,
     Running,
     Jumping,
+    Hit,
 }
 
 pub fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut loading: ResMut<LoadingAssets>,
 ) {
-    // Idle texture and atlas
-    let character_idle = asset_server.load("embedded://remrof/../assets/textures/idle.png");
-    let character_idle_layout = TextureAtlasLayout::from_grid(UVec2::splat(32), 11, 1, None, None);
-    let character_idle_handle = texture_atlas_layouts.add(character_idle_layout);
-
-    // Run texture and atlas
-    let character_run = asset_server.load("embedded://remrof/../assets/textures/run.png");
-    let character_run_layout = TextureAtlasLayout::from_grid(UVec2::splat(32), 12, 1, None, None);
-    let character_run_handle = texture_atlas_layouts.add(character_run_layout);
-
-    // Jump texture (no atlas, just a single image)
-    let character_jump = asset_server.load("embedded://remrof/../assets/textures/jump.png");
-
-    commands.insert_resource(CharacterAnimations {
-        idle_texture: character_idle.clone(),
-        idle_layout: character_idle_handle.clone(),
-        run_texture: character_run.clone(),
-        run_layout: character_run_handle,
-        jump_texture: character_jump.clone(),
+    let definitions = SKINS
+        .into_iter()
+        .map(|path| asset_server.load(asset_path(path)))
+        .collect::<Vec<_>>();
+    for definition in &definitions {
+        loading.add(definition.clone());
+    }
+    commands.insert_resource(CharacterAnimationsHandle(definitions));
+}
+
+/// Build the [`CharacterAnimations`] once all their definitions have loaded,
+/// and again whenever any of them changes.
+///
+/// Characters already in the game are switched over to the new animations
+/// where they stand.
+pub fn load_character_animations(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<CharacterAnimationDefinition>>,
+    handle: Res<CharacterAnimationsHandle>,
+    definitions: Res<Assets<CharacterAnimationDefinition>>,
+    mut skin_assets: SkinAssets,
+    mut characters: Query<
+        (
+            &Player,
+            &mut AnimationIndices,
+            &mut AnimationTimer,
+            &mut Sprite,
+        ),
+        With<Character>,
+    >,
+) {
+    let changed = events.read().any(|event| {
+        handle
+            .0
+            .iter()
+            .any(|handle| event.is_loaded_with_dependencies(handle) || event.is_modified(handle))
     });
+    if !changed {
+        return;
+    }
+    let Some(definitions) = handle
+        .0
+        .iter()
+        .map(|handle| definitions.get(handle))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return;
+    };
+
+    let animations = CharacterAnimations(
+        definitions
+            .into_iter()
+            .map(|definition| skin_assets.build(definition))
+            .collect(),
+    );
+
+    for (player, mut indices, mut timer, mut sprite) in &mut characters {
+        let skin = animations.for_player(*player);
+        *indices = skin.indices;
+        timer.set_duration(Duration::from_secs_f32(skin.frame_seconds));
+        sprite.color = skin.color;
+    }
+
+    commands.insert_resource(animations);
+}
+
+/// What it takes to turn a [`CharacterAnimationDefinition`] into a
+/// [`CharacterSkin`].
+#[derive(SystemParam)]
+pub struct SkinAssets<'w> {
+    asset_server: Res<'w, AssetServer>,
+    layouts: ResMut<'w, Assets<TextureAtlasLayout>>,
+    loading: Option<ResMut<'w, LoadingAssets>>,
+}
+
+impl SkinAssets<'_> {
+    fn build(&mut self, definition: &CharacterAnimationDefinition) -> CharacterSkin {
+        let frame_size = UVec2::splat(definition.frame_size);
+        let mut layout = |frames| {
+            self.layouts.add(TextureAtlasLayout::from_grid(
+                frame_size, frames, 1, None, None,
+            ))
+        };
+        let idle_layout = layout(definition.idle.frames);
+        let run_layout = layout(definition.run.frames);
+        let hit_layout = layout(definition.hit.frames);
+
+        CharacterSkin {
+            idle_texture: self.load(&definition.idle.texture),
+            idle_layout,
+            run_texture: self.load(&definition.run.texture),
+            run_layout,
+            hit_texture: self.load(&definition.hit.texture),
+            hit_layout,
+            jump_texture: self.load(&definition.jump),
+            indices: AnimationIndices {
+                idle: (0, definition.idle.frames.saturating_sub(1) as usize),
+                run: (0, definition.run.frames.saturating_sub(1) as usize),
+                hit: (0, definition.hit.frames.saturating_sub(1) as usize),
+            },
+            frame_seconds: definition.frame_seconds,
+            color: definition.color,
+        }
+    }
+
+    /// Load a texture, holding off gameplay until it's there if the game is
+    /// still loading.
+    fn load(&mut self, path: &str) -> Handle<Image> {
+        let texture = self.asset_server.load(asset_path(path.to_owned()));
+        if let Some(loading) = &mut self.loading {
+            loading.add(texture.clone());
+        }
+        texture
+    }
+}
+
+/// Spawn everyone who has joined so far at the [`RespawnPoint`].
+pub fn spawn_characters(
+    mut commands: Commands,
+    animations: Option<Res<CharacterAnimations>>,
+    respawn: Option<Res<RespawnPoint>>,
+    roster: Res<Roster>,
+) {
+    let (Some(animations), Some(respawn)) = (animations, respawn) else {
+        return;
+    };
+    spawn_roster(&mut commands, &animations, &respawn, &roster);
+}
+
+/// Start everyone over at the [`RespawnPoint`] when the level is restarted,
+/// rather than just the characters that are around, so that a restart doesn't
+/// kick anybody out of the game.
+pub fn restart_characters(
+    mut commands: Commands,
+    mut resets: EventReader<ResetLevel>,
+    characters: Query<Entity, With<Character>>,
+    animations: Option<Res<CharacterAnimations>>,
+    respawn: Option<Res<RespawnPoint>>,
+    roster: Res<Roster>,
+) {
+    if !resets.read().any(|ResetLevel(cause)| cause.is_restart()) {
+        return;
+    }
+    let (Some(animations), Some(respawn)) = (animations, respawn) else {
+        return;
+    };
+
+    for entity in &characters {
+        commands.entity(entity).despawn();
+    }
+    spawn_roster(&mut commands, &animations, &respawn, &roster);
+}
+
+fn spawn_roster(
+    commands: &mut Commands,
+    animations: &CharacterAnimations,
+    respawn: &RespawnPoint,
+    roster: &Roster,
+) {
+    for index in 0..roster.0.len() {
+        let player = Player(index);
+        spawn_character(commands, animations, player, respawn.for_player(player));
+    }
+}
+
+/// Spawn the character for a single player.
+pub fn spawn_character(
+    commands: &mut Commands,
+    animations: &CharacterAnimations,
+    player: Player,
+    position: Vec2,
+) {
+    let animations = animations.for_player(player);
+    let mut sprite = Sprite::from_atlas_image(
+        animations.idle_texture.clone(),
+        TextureAtlas {
+            layout: animations.idle_layout.clone(),
+            index: 0,
+        },
+    );
+    sprite.color = animations.color;
 
     // Our lil' character
     commands.spawn((
-        Sprite::from_atlas_image(
-            character_idle,
-            TextureAtlas {
-                layout: character_idle_handle,
-                index: 0,
-            },
-        ),
+        sprite,
         Transform {
-            scale: Vec3::splat(1.5),
-            translation: Vec3::new(0.0, 0.0, 100.0),
+            scale: Vec3::splat(PIXEL_SCALE),
+            translation: position.extend(100.0),
             ..default()
         },
-        AnimationIndices {
-            idle: (0, 10),
-            run: (0, 11),
-        },
-        AnimationTimer(Timer::from_seconds(0.1, TimerMode::Repeating)),
+        animations.indices,
+        AnimationTimer(Timer::from_seconds(
+            animations.frame_seconds,
+            TimerMode::Repeating,
+        )),
         Character,
+        player,
+        CharacterIntent::default(),
+        FallSpeed::default(),
         CharacterState::Idle,
+        PhysicsPosition::new(position),
         Velocity {
             x: 0.0,
             y: 0.0,
@@ -79,17 +276,19 @@ pub fn setup(
         },
         Grounded,
         Collider {
-            size: Vec2::new(32.0 * 1.1, 32.0 * 1.5),
+            size: Vec2::new(32.0 * 1.1, 32.0 * PIXEL_SCALE),
             offset: Vec2::new(0.0, -5.0),
         },
-        RestartRespawn,
+        CollisionLayer(Layer::Characters),
+        Health(MAX_HEALTH),
     ));
 }
 
 pub fn animate_character(
     time: Res<Time>,
-    animations: Res<CharacterAnimations>,
+    animations: Option<Res<CharacterAnimations>>,
     mut query: Query<(
+        &Player,
         &mut CharacterState,
         &Velocity,
         &AnimationIndices,
@@ -97,7 +296,12 @@ pub fn animate_character(
         &mut Sprite,
     )>,
 ) {
-    for (mut state, velocity, indices, mut timer, mut sprite) in &mut query {
+    let Some(animations) = animations else {
+        return;
+    };
+
+    for (player, mut state, velocity, indices, mut timer, mut sprite) in &mut query {
+        let animations = animations.for_player(*player);
         let (texture, layout, current_indices) = match *state {
             CharacterState::Idle => (
                 &animations.idle_texture,
@@ -109,6 +313,11 @@ pub fn animate_character(
                 Some(&animations.run_layout),
                 indices.run,
             ),
+            CharacterState::Hit => (
+                &animations.hit_texture,
+                Some(&animations.hit_layout),
+                indices.hit,
+            ),
             CharacterState::Jumping => {
                 // If we're not jumping anymore, so reset to something else.
                 if velocity.is_grounded {
@@ -163,18 +372,76 @@ pub fn animate_character(
     }
 }
 
-pub fn move_character(
+/// What a player is asking their character to do.
+///
+/// Input is sampled every frame but acted on in `FixedUpdate`, which can run
+/// any number of times per frame (including zero), so presses are held here
+/// until a fixed step gets around to them.
+#[derive(Component, Default)]
+pub struct CharacterIntent {
+    /// See [`crate::input::PlayerInput::horizontal_axis`].
+    pub axis: f32,
+    /// Jump was pressed since the last fixed step.
+    pub jump: bool,
+}
+
+/// Sample each player's input into their character's [`CharacterIntent`].
+///
+/// This runs right before the fixed loop so that the fixed steps of a frame
+/// see that frame's input.
+pub fn read_input(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(&mut Velocity, &mut CharacterState, &mut Sprite), With<Character>>,
+    gamepads: Query<&Gamepad>,
+    roster: Res<Roster>,
+    mut query: Query<(&Player, &mut CharacterIntent)>,
 ) {
-    let running_speed = 300.0;
-    let acceleration = 20.0;
-    let deceleration = 40.0;
+    for (player, mut intent) in &mut query {
+        let Some(input) = roster.0.get(player.0) else {
+            continue;
+        };
 
-    let query_res = query.get_single_mut();
-    if let Ok((mut velocity, mut state, mut sprite)) = query_res {
-        let move_left = keyboard_input.pressed(KeyCode::ArrowLeft);
-        let move_right = keyboard_input.pressed(KeyCode::ArrowRight);
+        intent.axis = input.horizontal_axis(&keyboard_input, &gamepads);
+        intent.jump |= input.jump_just_pressed(&keyboard_input, &gamepads);
+    }
+}
+
+pub fn move_character(
+    time: Res<Time>,
+    tuning: Option<Res<MovementTuning>>,
+    mut query: Query<
+        (
+            &CharacterIntent,
+            &mut Velocity,
+            &mut CharacterState,
+            &mut Sprite,
+            Has<Hurt>,
+        ),
+        With<Character>,
+    >,
+) {
+    let Some(tuning) = tuning else {
+        return;
+    };
+
+    for (intent, mut velocity, mut state, mut sprite, hurt) in &mut query {
+        // Let the knockback play out.
+        if hurt {
+            *state = CharacterState::Hit;
+            continue;
+        }
+
+        let control = if velocity.is_grounded {
+            tuning.ground
+        } else {
+            tuning.air
+        };
+
+        let axis = intent.axis;
+        let move_left = axis < 0.0;
+        let move_right = axis > 0.0;
+
+        let acceleration = control.acceleration * time.delta_secs();
+        let deceleration = control.deceleration * time.delta_secs();
 
         if move_left {
             velocity.x -= acceleration;
@@ -186,7 +453,13 @@ pub fn move_character(
             velocity.x = (velocity.x + deceleration).min(0.0);
         }
 
-        velocity.x = velocity.x.clamp(-running_speed, running_speed);
+        // A half-pushed stick should only get you to half speed.
+        let max_speed = if move_left || move_right {
+            tuning.running_speed * axis.abs()
+        } else {
+            tuning.running_speed
+        };
+        velocity.x = velocity.x.clamp(-max_speed, max_speed);
 
         if move_left {
             sprite.flip_x = true;
@@ -204,19 +477,164 @@ pub fn move_character(
     }
 }
 
+/// How fast a character has fallen since it left the ground.
+#[derive(Component, Default)]
+pub struct FallSpeed(f32);
+
+/// Landing faster than this shakes the camera.
+const HARD_LANDING_SPEED: f32 = 900.0;
+
+/// Shake the camera when somebody lands hard, harder the faster they fell.
+pub fn shake_on_hard_landing(
+    mut query: Query<(&Velocity, &mut FallSpeed), With<Character>>,
+    mut camera_effects: EventWriter<CameraEffect>,
+) {
+    for (velocity, mut fall_speed) in &mut query {
+        if !velocity.is_grounded {
+            fall_speed.0 = fall_speed.0.max(-velocity.y);
+            continue;
+        }
+
+        if fall_speed.0 > HARD_LANDING_SPEED {
+            let trauma = ((fall_speed.0 - HARD_LANDING_SPEED) / 1000.0).min(0.6);
+            camera_effects.send(CameraEffect::Shake(trauma));
+            camera_effects.send(CameraEffect::ZoomPunch(0.05));
+        }
+        fall_speed.0 = 0.0;
+    }
+}
+
 pub fn jump(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut query: Query<&mut Velocity, With<Character>>,
+    tuning: Option<Res<MovementTuning>>,
+    mut query: Query<(&mut CharacterIntent, &mut Velocity, Has<Hurt>), With<Character>>,
 ) {
-    if !keyboard_input.just_pressed(KeyCode::Space) {
+    let Some(tuning) = tuning else {
         return;
-    }
+    };
+
+    for (mut intent, mut velocity, hurt) in &mut query {
+        if !intent.jump {
+            continue;
+        }
+        intent.jump = false;
+
+        if hurt {
+            continue;
+        }
 
-    let velocity = query.get_single_mut();
-    if let Ok(mut velocity) = velocity {
         // TODO: Give some small buffer if we're moving downward.
         if velocity.is_grounded {
-            velocity.y = 500.0;
+            velocity.y = tuning.jump_velocity;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{app::RunFixedMainLoopSystem, time::TimeUpdateStrategy};
+
+    use super::*;
+    use crate::{input::Roster, physics, platform::Platform};
+
+    /// The tuning the game ships with.
+    fn tuning() -> MovementTuning {
+        ron::from_str(include_str!("../assets/tuning/movement.tuning.ron")).unwrap()
+    }
+
+    /// Run the character for one second at the given frame rate, holding
+    /// right and jump, and return where it was at the end of every 1/32 of a
+    /// second.
+    ///
+    /// Nothing clears the keyboard between frames here, so jump stays "just
+    /// pressed" and the character hops every time it lands.
+    fn trajectory(frames_per_second: u32) -> Vec<Vec2> {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(
+                Duration::from_secs(1) / frames_per_second,
+            ))
+            .insert_resource(tuning())
+            .init_resource::<Roster>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_event::<physics::Collided>()
+            .add_systems(
+                RunFixedMainLoop,
+                read_input.in_set(RunFixedMainLoopSystem::BeforeFixedMainLoop),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    jump,
+                    move_character,
+                    physics::apply_velocity,
+                    physics::apply_gravity,
+                    physics::check_for_collisions,
+                )
+                    .chain(),
+            );
+
+        app.world_mut().spawn((
+            Platform,
+            Transform::from_translation(Vec3::new(0.0, -50.0, 0.0)),
+            Collider {
+                size: Vec2::new(1000.0, 30.0),
+                ..default()
+            },
+        ));
+        let character = app
+            .world_mut()
+            .spawn((
+                Character,
+                Player(0),
+                CharacterIntent::default(),
+                CharacterState::Idle,
+                Sprite::default(),
+                Transform::default(),
+                PhysicsPosition::new(Vec2::ZERO),
+                Velocity {
+                    x: 0.0,
+                    y: 0.0,
+                    is_grounded: true,
+                },
+                Grounded,
+                Collider {
+                    size: Vec2::new(32.0 * 1.1, 32.0 * 1.5),
+                    offset: Vec2::new(0.0, -5.0),
+                },
+            ))
+            .id();
+
+        let mut keyboard_input = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keyboard_input.press(KeyCode::ArrowRight);
+        keyboard_input.press(KeyCode::Space);
+
+        // The very first update has no time pass, so get it out of the way.
+        app.update();
+
+        let frames_per_sample = frames_per_second / 32;
+        let mut positions = Vec::new();
+        for frame in 1..=frames_per_second {
+            app.update();
+            if frame % frames_per_sample == 0 {
+                let position = app.world().get::<PhysicsPosition>(character).unwrap();
+                positions.push(position.current);
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn movement_is_frame_rate_independent() {
+        let slow = trajectory(32);
+        let fast = trajectory(256);
+
+        assert_eq!(slow.len(), 32);
+        assert_eq!(slow, fast);
+
+        // Make sure we actually went somewhere, and jumped along the way.
+        assert!(slow.last().unwrap().x > 100.0);
+        assert!(slow.iter().any(|position| position.y > 50.0));
+    }
+}
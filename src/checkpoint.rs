@@ -0,0 +1,172 @@
+//! Checkpoints, which move where characters respawn.
+//!
+//! Touching a checkpoint moves the [`RespawnPoint`] to it. A checkpoint can
+//! also take a snapshot of the [`LevelState`], which a restart goes back to,
+//! so that restarting undoes whatever was done in the level since then.
+
+use bevy::{math::bounding::IntersectsVolume, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    character::Character,
+    health::Dead,
+    input::Player,
+    level::{LevelEntity, LevelKey, LevelState},
+    physics::{Collider, PhysicsPosition, Sensor},
+    restart::RestartPolicy,
+};
+
+/// A checkpoint, as written in a level file.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct CheckpointDefinition {
+    /// Where the bottom of the flagpole is.
+    pub position: Vec2,
+    /// Whether to snapshot the level's state when the checkpoint is reached.
+    #[serde(default)]
+    pub snapshot: bool,
+    /// Reached checkpoints stay reached by default.
+    #[serde(default = "default_restart")]
+    pub restart: RestartPolicy,
+}
+
+fn default_restart() -> RestartPolicy {
+    RestartPolicy::Persist
+}
+
+#[derive(Component)]
+pub struct Checkpoint {
+    snapshot: bool,
+}
+
+/// Marks a [`Checkpoint`] that somebody has touched.
+#[derive(Component)]
+pub struct Reached;
+
+/// The flag on a [`Checkpoint`]'s pole, which changes color once it's reached.
+#[derive(Component)]
+pub struct Flag;
+
+/// Where characters respawn, and what a restart goes back to.
+#[derive(Resource, Debug)]
+pub struct RespawnPoint {
+    /// Where the level starts.
+    pub start: Vec2,
+    /// Where the latest checkpoint is, or the start if there isn't one.
+    pub position: Vec2,
+    /// The level's state as of the latest checkpoint that took a snapshot.
+    pub snapshot: Option<LevelState>,
+}
+
+impl RespawnPoint {
+    pub fn new(start: Vec2) -> Self {
+        RespawnPoint {
+            start,
+            position: start,
+            snapshot: None,
+        }
+    }
+
+    /// Where a player's character respawns, a little to the right of the
+    /// players who joined before them.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn for_player(&self, player: Player) -> Vec2 {
+        self.position + Vec2::new(player.0 as f32 * 40.0, 0.0)
+    }
+}
+
+const POLE_SIZE: Vec2 = Vec2::new(4.0, 48.0);
+const FLAG_SIZE: Vec2 = Vec2::new(20.0, 14.0);
+const FLAG_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+const REACHED_FLAG_COLOR: Color = Color::srgb(0.4, 0.8, 0.3);
+
+/// How high above a checkpoint's base characters respawn, so that they drop
+/// onto the ground rather than appearing inside it.
+const RESPAWN_HEIGHT: f32 = 40.0;
+
+pub fn spawn_checkpoint(
+    commands: &mut Commands,
+    definition: &CheckpointDefinition,
+    key: LevelKey,
+    reached: bool,
+) {
+    let center = definition.position + Vec2::Y * POLE_SIZE.y / 2.0;
+    let mut checkpoint = commands.spawn((
+        Sprite::from_color(Color::srgb(0.3, 0.3, 0.3), POLE_SIZE),
+        Transform::from_translation(center.extend(1.0)),
+        Checkpoint {
+            snapshot: definition.snapshot,
+        },
+        Collider {
+            size: Vec2::new(24.0, POLE_SIZE.y),
+            ..default()
+        },
+        Sensor,
+        key,
+        definition.restart,
+        LevelEntity,
+    ));
+    checkpoint.with_child((
+        Sprite::from_color(FLAG_COLOR, FLAG_SIZE),
+        Transform::from_translation(Vec3::new(
+            // Hanging off the right of the pole, at the top.
+            POLE_SIZE.x / 2.0 + FLAG_SIZE.x / 2.0,
+            (POLE_SIZE.y - FLAG_SIZE.y) / 2.0,
+            0.0,
+        )),
+        Flag,
+    ));
+    if reached {
+        checkpoint.insert(Reached);
+    }
+}
+
+/// Reach checkpoints that characters touch.
+pub fn touch_checkpoints(
+    mut commands: Commands,
+    characters: Query<(&PhysicsPosition, &Collider), (With<Character>, Without<Dead>)>,
+    checkpoints: Query<
+        (
+            Entity,
+            &Transform,
+            &Collider,
+            &Checkpoint,
+            &LevelKey,
+            &RestartPolicy,
+        ),
+        Without<Reached>,
+    >,
+    mut respawn: ResMut<RespawnPoint>,
+    mut state: ResMut<LevelState>,
+) {
+    for (entity, transform, collider, checkpoint, key, policy) in &checkpoints {
+        let area = collider.aabb(transform.translation.truncate());
+        let touched = characters.iter().any(|(position, character_collider)| {
+            character_collider.aabb(position.current).intersects(&area)
+        });
+        if !touched {
+            continue;
+        }
+
+        commands.entity(entity).insert(Reached);
+        state.clear(*key, *policy);
+
+        let base = transform.translation.truncate() - Vec2::Y * POLE_SIZE.y / 2.0;
+        respawn.position = base + Vec2::Y * RESPAWN_HEIGHT;
+        if checkpoint.snapshot {
+            respawn.snapshot = Some(state.clone());
+        }
+    }
+}
+
+/// Change a checkpoint's flag once it's reached.
+pub fn raise_flags(
+    checkpoints: Query<&Children, Added<Reached>>,
+    mut flags: Query<&mut Sprite, With<Flag>>,
+) {
+    for children in &checkpoints {
+        let mut iter = flags.iter_many_mut(children);
+        while let Some(mut flag) = iter.fetch_next() {
+            flag.color = REACHED_FLAG_COLOR;
+        }
+    }
+}
@@ -0,0 +1,492 @@
+//! Enemies, which hurt characters who run into them.
+//!
+//! What each kind of enemy looks like and how it behaves comes from
+//! `assets/enemies/kinds.enemies.ron`, so a new kind is mostly a new entry
+//! there. Levels place enemies by the name of their kind. How they decide
+//! what to do is up to the `ai` module.
+//!
+//! Enemies are [`Grounded`], so they fall and stand on platforms like the
+//! characters do. Landing on one from above defeats it, as does being hit by
+//! a projectile that hits enemies; touching it any other way hurts.
+
+use std::collections::HashMap;
+
+use bevy::{
+    math::bounding::{BoundingVolume, IntersectsVolume},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    ai::{self, Brain, Perception, Rule},
+    animation::{LoopingAnimation, SpriteSheet},
+    assets::asset_path,
+    character::Character,
+    health::{Damage, Dead},
+    level::{KillHeight, LevelEntity, LevelKey, LevelState},
+    loading::LoadingAssets,
+    physics::{Collider, CollisionLayer, Grounded, Layer, PhysicsPosition, Velocity},
+    pixel_perfect::PIXEL_SCALE,
+    projectile::{ProjectileDefinition, ProjectileHit, Shoot},
+    restart::RestartPolicy,
+};
+
+/// Every kind of enemy, as written in its `.enemies.ron` file.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct EnemyKindsDefinition {
+    pub kinds: HashMap<String, EnemyKindDefinition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemyKindDefinition {
+    /// The width and height of every frame, in pixels.
+    pub frame_size: UVec2,
+    pub frame_seconds: f32,
+    /// Played while it's standing still, if it has one. Otherwise it's
+    /// always running.
+    #[serde(default)]
+    pub idle: Option<SpriteSheet>,
+    pub run: SpriteSheet,
+    /// Played once it's been stomped on, as it falls out of the level.
+    pub hit: SpriteSheet,
+    #[serde(default = "default_color")]
+    pub color: Color,
+    /// The size of its hitbox, in the sprite's pixels.
+    pub size: Vec2,
+    /// How far the hitbox is from the middle of the sprite, in its pixels.
+    #[serde(default)]
+    pub offset: Vec2,
+    /// How fast it walks, in units per second.
+    pub speed: f32,
+    /// Whether it minds the edges of platforms, rather than walking off.
+    #[serde(default)]
+    pub turns_at_ledges: bool,
+    /// How fast it jumps, if it can, when a wall or ledge is in the way of
+    /// where it's headed. Patrolling enemies turn around instead.
+    #[serde(default)]
+    pub jump_velocity: Option<f32>,
+    /// How far away it can see characters, in units.
+    #[serde(default)]
+    pub sight: f32,
+    /// What it does, and when. It patrols if this is left out.
+    #[serde(default = "ai::default_behavior")]
+    pub behavior: Vec<Rule>,
+    #[serde(default = "default_damage")]
+    pub damage: u32,
+    /// What it shoots, if it has a `Shoot` rule.
+    #[serde(default)]
+    pub projectile: Option<ProjectileDefinition>,
+    /// How fast a character that stomps on it bounces back up.
+    #[serde(default = "default_bounce")]
+    pub bounce: f32,
+}
+
+fn default_color() -> Color {
+    Color::WHITE
+}
+
+fn default_damage() -> u32 {
+    1
+}
+
+fn default_bounce() -> f32 {
+    400.0
+}
+
+/// An enemy, as written in a level file.
+#[derive(Component, Debug, Clone, Deserialize)]
+pub struct EnemyDefinition {
+    /// The name of its kind in the `.enemies.ron` file.
+    pub kind: String,
+    /// Where it starts. It falls onto whatever is below.
+    pub position: Vec2,
+    #[serde(default)]
+    pub facing: Facing,
+    #[serde(default)]
+    pub restart: RestartPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Facing {
+    #[default]
+    Left,
+    Right,
+}
+
+/// The file the [`EnemyKinds`] resource is kept up to date with.
+#[derive(Resource)]
+pub struct EnemyKindsHandle(Handle<EnemyKindsDefinition>);
+
+/// Every kind of enemy, ready to be spawned.
+#[derive(Resource)]
+pub struct EnemyKinds(HashMap<String, EnemyKind>);
+
+impl EnemyKinds {
+    pub fn get(&self, name: &str) -> Option<&EnemyKind> {
+        self.0.get(name)
+    }
+}
+
+pub struct EnemyKind {
+    pub definition: EnemyKindDefinition,
+    idle: Option<(Handle<Image>, Handle<TextureAtlasLayout>)>,
+    run_texture: Handle<Image>,
+    run_layout: Handle<TextureAtlasLayout>,
+    hit_texture: Handle<Image>,
+    hit_layout: Handle<TextureAtlasLayout>,
+    projectile_texture: Option<Handle<Image>>,
+}
+
+impl EnemyKind {
+    /// A shot of its projectile by `owner`, if it has one.
+    pub fn shoot(&self, owner: Entity, origin: Vec2, direction: Dir2) -> Option<Shoot> {
+        let definition = self.definition.projectile.as_ref()?;
+        Some(Shoot {
+            owner,
+            origin,
+            direction,
+            definition: definition.clone(),
+            texture: self.projectile_texture.clone()?,
+        })
+    }
+}
+
+#[derive(Component)]
+pub struct Enemy {
+    /// The name of its kind.
+    pub kind: String,
+    /// -1 for left, 1 for right.
+    pub facing: f32,
+}
+
+/// An enemy that has been stomped on or shot, and is on its way out of the
+/// level.
+#[derive(Component)]
+pub struct Defeated;
+
+/// How fast a defeated enemy hops up before falling out of the level.
+const DEFEATED_HOP: f32 = 300.0;
+
+/// How far into an enemy a character can have sunk in one step and still
+/// count as landing on top of it.
+const STOMP_TOLERANCE: f32 = 8.0;
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut loading: ResMut<LoadingAssets>,
+) {
+    let kinds = asset_server.load(asset_path("enemies/kinds.enemies.ron"));
+    loading.add(kinds.clone());
+    commands.insert_resource(EnemyKindsHandle(kinds));
+}
+
+/// Build the [`EnemyKinds`] once their definition has loaded, and again
+/// whenever it changes. Enemies already in the level keep their old kind
+/// until the level is spawned again.
+pub fn load_enemy_kinds(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<EnemyKindsDefinition>>,
+    handle: Res<EnemyKindsHandle>,
+    definitions: Res<Assets<EnemyKindsDefinition>>,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut loading: Option<ResMut<LoadingAssets>>,
+) {
+    let changed = events
+        .read()
+        .any(|event| event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0));
+    if !changed {
+        return;
+    }
+    let Some(definitions) = definitions.get(&handle.0) else {
+        return;
+    };
+
+    let mut kinds = HashMap::new();
+    for (name, definition) in &definitions.kinds {
+        let idle = definition.idle.as_ref().map(|idle| {
+            (
+                asset_server.load(asset_path(idle.texture.clone())),
+                layouts.add(TextureAtlasLayout::from_grid(
+                    definition.frame_size,
+                    idle.frames,
+                    1,
+                    None,
+                    None,
+                )),
+            )
+        });
+        let kind = EnemyKind {
+            definition: definition.clone(),
+            idle,
+            run_texture: asset_server.load(asset_path(definition.run.texture.clone())),
+            run_layout: layouts.add(TextureAtlasLayout::from_grid(
+                definition.frame_size,
+                definition.run.frames,
+                1,
+                None,
+                None,
+            )),
+            hit_texture: asset_server.load(asset_path(definition.hit.texture.clone())),
+            hit_layout: layouts.add(TextureAtlasLayout::from_grid(
+                definition.frame_size,
+                definition.hit.frames,
+                1,
+                None,
+                None,
+            )),
+            projectile_texture: definition
+                .projectile
+                .as_ref()
+                .map(|projectile| asset_server.load(asset_path(projectile.texture.clone()))),
+        };
+        if let Some(loading) = &mut loading {
+            if let Some((texture, _)) = &kind.idle {
+                loading.add(texture.clone());
+            }
+            if let Some(texture) = &kind.projectile_texture {
+                loading.add(texture.clone());
+            }
+            loading.add(kind.run_texture.clone());
+            loading.add(kind.hit_texture.clone());
+        }
+        kinds.insert(name.clone(), kind);
+    }
+
+    commands.insert_resource(EnemyKinds(kinds));
+}
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    definition: &EnemyDefinition,
+    key: LevelKey,
+    defeated: bool,
+) {
+    if !defeated {
+        commands.spawn((definition.clone(), key, definition.restart, LevelEntity));
+    }
+}
+
+/// Give enemies spawned by the level their sprites and bodies, once their
+/// kinds have loaded.
+pub fn build_enemies(
+    mut commands: Commands,
+    enemies: Query<(Entity, &EnemyDefinition), Without<Enemy>>,
+    kinds: Option<Res<EnemyKinds>>,
+) {
+    let Some(kinds) = kinds else {
+        return;
+    };
+
+    for (entity, definition) in &enemies {
+        let Some(kind) = kinds.get(&definition.kind) else {
+            error!("There's no kind of enemy called {:?}", definition.kind);
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+
+        let mut sprite = Sprite::from_atlas_image(
+            kind.run_texture.clone(),
+            TextureAtlas {
+                layout: kind.run_layout.clone(),
+                index: 0,
+            },
+        );
+        sprite.color = kind.definition.color;
+
+        commands.entity(entity).insert((
+            sprite,
+            Transform {
+                translation: definition.position.extend(50.0),
+                scale: Vec3::splat(PIXEL_SCALE),
+                ..default()
+            },
+            LoopingAnimation::new(
+                kind.definition.run.frames as usize,
+                kind.definition.frame_seconds,
+            ),
+            Enemy {
+                kind: definition.kind.clone(),
+                facing: match definition.facing {
+                    Facing::Left => -1.0,
+                    Facing::Right => 1.0,
+                },
+            },
+            PhysicsPosition::new(definition.position),
+            Velocity {
+                x: 0.0,
+                y: 0.0,
+                is_grounded: false,
+            },
+            Grounded,
+            Collider {
+                size: kind.definition.size * PIXEL_SCALE,
+                offset: kind.definition.offset * PIXEL_SCALE,
+            },
+            CollisionLayer(Layer::Enemies),
+            Perception::default(),
+            Brain::default(),
+        ));
+    }
+}
+
+/// Defeat enemies that characters land on, bouncing the characters off them,
+/// and hurt characters who run into enemies any other way.
+pub fn stomp_or_hurt(
+    mut commands: Commands,
+    mut characters: Query<
+        (Entity, &PhysicsPosition, &Collider, &mut Velocity),
+        (With<Character>, Without<Dead>, Without<Enemy>),
+    >,
+    enemies: Query<(Entity, &Enemy, &PhysicsPosition, &Collider), Without<Defeated>>,
+    kinds: Option<Res<EnemyKinds>>,
+    mut damage: EventWriter<Damage>,
+) {
+    let Some(kinds) = kinds else {
+        return;
+    };
+
+    for (entity, enemy, enemy_position, enemy_collider) in &enemies {
+        let Some(kind) = kinds.get(&enemy.kind) else {
+            continue;
+        };
+        let body = enemy_collider.aabb(enemy_position.current);
+
+        for (character, position, collider, mut velocity) in &mut characters {
+            if !collider.aabb(position.current).intersects(&body) {
+                continue;
+            }
+
+            let was_above = collider.aabb(position.previous).min.y >= body.max.y - STOMP_TOLERANCE;
+            if velocity.y >= 0.0 || !was_above {
+                damage.send(Damage {
+                    target: character,
+                    amount: kind.definition.damage,
+                    source: body.center(),
+                });
+                continue;
+            }
+
+            velocity.y = kind.definition.bounce;
+            velocity.is_grounded = false;
+            commands.entity(entity).insert(Defeated);
+            break;
+        }
+    }
+}
+
+/// Defeat enemies that projectiles hit.
+pub fn defeat_shot_enemies(
+    mut commands: Commands,
+    mut hits: EventReader<ProjectileHit>,
+    enemies: Query<(), (With<Enemy>, Without<Defeated>)>,
+) {
+    for hit in hits.read() {
+        if let Some(target) = hit.target.filter(|target| enemies.contains(*target)) {
+            commands.entity(target).insert(Defeated);
+        }
+    }
+}
+
+/// Send newly defeated enemies out of the level.
+pub fn knock_out(
+    mut commands: Commands,
+    mut enemies: Query<
+        (
+            Entity,
+            &Enemy,
+            &LevelKey,
+            &RestartPolicy,
+            &mut Velocity,
+            &mut Sprite,
+            &mut LoopingAnimation,
+        ),
+        Added<Defeated>,
+    >,
+    kinds: Option<Res<EnemyKinds>>,
+    mut state: ResMut<LevelState>,
+) {
+    let Some(kinds) = kinds else {
+        return;
+    };
+
+    for (entity, enemy, key, policy, mut velocity, mut sprite, mut animation) in &mut enemies {
+        let Some(kind) = kinds.get(&enemy.kind) else {
+            continue;
+        };
+
+        // It stops being something to stand on or bump into, and drops out
+        // of the level.
+        commands.entity(entity).remove::<(Grounded, Collider)>();
+        velocity.x = 0.0;
+        velocity.y = DEFEATED_HOP;
+        velocity.is_grounded = false;
+        sprite.image = kind.hit_texture.clone();
+        sprite.texture_atlas = Some(TextureAtlas {
+            layout: kind.hit_layout.clone(),
+            index: 0,
+        });
+        animation.frames = kind.definition.hit.frames as usize;
+        animation.timer.reset();
+        state.clear(*key, *policy);
+    }
+}
+
+/// Show enemies standing still, if their kind has an animation for it, or
+/// running otherwise.
+pub fn animate_enemies(
+    mut enemies: Query<(&Enemy, &Velocity, &mut Sprite, &mut LoopingAnimation), Without<Defeated>>,
+    kinds: Option<Res<EnemyKinds>>,
+) {
+    let Some(kinds) = kinds else {
+        return;
+    };
+
+    for (enemy, velocity, mut sprite, mut animation) in &mut enemies {
+        let Some(kind) = kinds.get(&enemy.kind) else {
+            continue;
+        };
+        let (texture, layout, frames) = match &kind.idle {
+            Some((texture, layout)) if velocity.x == 0.0 => (
+                texture,
+                layout,
+                kind.definition.idle.as_ref().map_or(1, |idle| idle.frames),
+            ),
+            _ => (
+                &kind.run_texture,
+                &kind.run_layout,
+                kind.definition.run.frames,
+            ),
+        };
+        if sprite.image == *texture {
+            continue;
+        }
+
+        sprite.image = texture.clone();
+        sprite.texture_atlas = Some(TextureAtlas {
+            layout: layout.clone(),
+            index: 0,
+        });
+        animation.frames = frames as usize;
+        animation.timer.reset();
+    }
+}
+
+/// Get rid of enemies that have fallen out of the level, whether they were
+/// defeated or walked off a ledge.
+pub fn despawn_fallen_enemies(
+    mut commands: Commands,
+    enemies: Query<(Entity, &PhysicsPosition), With<Enemy>>,
+    kill_height: Option<Res<KillHeight>>,
+) {
+    let Some(kill_height) = kill_height else {
+        return;
+    };
+
+    for (entity, position) in &enemies {
+        if position.current.y < kill_height.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,448 @@
+//! Fruit, which is there to be collected.
+//!
+//! A collected fruit is part of the [`LevelState`], so by default it comes
+//! back if the level is reset to before it was collected. Separately, the
+//! [`FruitCollection`] remembers every fruit that has ever been collected in
+//! each level, however many times it has been reset. Only the first time a
+//! fruit is collected adds to the [`Score`], and fruit that has been collected
+//! before is drawn faded when it comes back. The collection is saved to
+//! [`SAVE_PATH`] whenever it grows, and loaded from there at startup, so it
+//! carries over from one run to the next.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bevy::{
+    ecs::system::SystemParam, math::bounding::IntersectsVolume, prelude::*, utils::HashMap,
+};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    animation::LoopingAnimation,
+    assets::asset_path,
+    block::Boxed,
+    character::Character,
+    health::Dead,
+    level::{CurrentLevel, LevelEntity, LevelKey, LevelState},
+    loading::LoadingAssets,
+    physics::{Collider, PhysicsPosition, Sensor},
+    pixel_perfect::PIXEL_SCALE,
+    restart::RestartPolicy,
+};
+
+/// A fruit, as written in a level file.
+#[derive(Component, Debug, Clone, Copy, Deserialize)]
+pub struct FruitDefinition {
+    pub position: Vec2,
+    #[serde(default)]
+    pub kind: FruitKind,
+    #[serde(default)]
+    pub restart: RestartPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum FruitKind {
+    #[default]
+    Apple,
+    Bananas,
+    Cherries,
+    Melon,
+}
+
+impl FruitKind {
+    const ALL: [FruitKind; 4] = [
+        FruitKind::Apple,
+        FruitKind::Bananas,
+        FruitKind::Cherries,
+        FruitKind::Melon,
+    ];
+
+    fn texture(self) -> &'static str {
+        match self {
+            FruitKind::Apple => "textures/apple.png",
+            FruitKind::Bananas => "textures/bananas.png",
+            FruitKind::Cherries => "textures/cherries.png",
+            FruitKind::Melon => "textures/melon.png",
+        }
+    }
+
+    /// What collecting it for the first time adds to the [`Score`].
+    fn points(self) -> u32 {
+        match self {
+            FruitKind::Apple | FruitKind::Bananas => 1,
+            FruitKind::Cherries => 2,
+            FruitKind::Melon => 5,
+        }
+    }
+}
+
+/// Marks a fruit that has been collected. It stays around, hidden, so that it
+/// still counts towards the level's total.
+#[derive(Component)]
+pub struct Collected;
+
+/// A fruit playing its collected animation, which is hidden once this runs
+/// out.
+#[derive(Component, Deref, DerefMut)]
+pub struct Vanishing(Timer);
+
+/// How many of the current level's fruits are collected, out of how many.
+#[derive(Resource, Debug, Default, PartialEq, Eq)]
+pub struct FruitCount {
+    pub collected: usize,
+    pub total: usize,
+}
+
+/// Points for every fruit collected, counting each fruit only once.
+#[derive(Resource, Debug, Default)]
+pub struct Score(pub u32);
+
+/// Where the [`FruitCollection`] is saved, relative to the working directory.
+pub const SAVE_PATH: &str = "save.ron";
+
+/// Every fruit that has ever been collected, by the path of its level and the
+/// fruit's [`LevelKey`].
+#[derive(Resource, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FruitCollection(BTreeMap<String, BTreeSet<(String, usize)>>);
+
+impl FruitCollection {
+    /// Load the collection saved by an earlier run, or start a new one if
+    /// there isn't one.
+    pub fn load() -> Self {
+        let save = match std::fs::read_to_string(SAVE_PATH) {
+            Ok(save) => save,
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Couldn't read {SAVE_PATH}: {err}");
+                }
+                return FruitCollection::default();
+            }
+        };
+        ron::from_str(&save).unwrap_or_else(|err| {
+            warn!("Couldn't parse {SAVE_PATH}, starting over: {err}");
+            FruitCollection::default()
+        })
+    }
+
+    pub fn contains(&self, level: &str, key: LevelKey) -> bool {
+        self.0
+            .get(level)
+            .is_some_and(|keys| keys.contains(&(key.0.to_owned(), key.1)))
+    }
+
+    /// Add a fruit to the collection, returning whether it's new.
+    pub fn insert(&mut self, level: &str, key: LevelKey) -> bool {
+        self.0
+            .entry(level.to_owned())
+            .or_default()
+            .insert((key.0.to_owned(), key.1))
+    }
+}
+
+/// The [`FruitCollection`] and [`Score`], as kept up to date for the current
+/// level.
+#[derive(SystemParam)]
+pub struct Progress<'w> {
+    current: Res<'w, CurrentLevel>,
+    collection: ResMut<'w, FruitCollection>,
+    score: ResMut<'w, Score>,
+}
+
+impl Progress<'_> {
+    /// Add a fruit to the collection, scoring it if it's the first time.
+    ///
+    /// The collection only counts as changed if the fruit is new to it, so
+    /// that it's only saved when there's something to save.
+    fn collect(&mut self, key: LevelKey, kind: FruitKind) {
+        let level = self.current.path();
+        if self
+            .collection
+            .bypass_change_detection()
+            .insert(&level, key)
+        {
+            self.collection.set_changed();
+            self.score.0 += kind.points();
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct FruitTextures {
+    fruits: HashMap<FruitKind, Handle<Image>>,
+    layout: Handle<TextureAtlasLayout>,
+    collected: Handle<Image>,
+    collected_layout: Handle<TextureAtlasLayout>,
+}
+
+const FRAME_SIZE: u32 = 32;
+const FRAMES: usize = 17;
+const COLLECTED_FRAMES: usize = 6;
+const FRAME_SECONDS: f32 = 0.05;
+
+/// A fruit's faded color, for when it has been collected before.
+const SEEN_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.5);
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut loading: ResMut<LoadingAssets>,
+) {
+    let frame_size = UVec2::splat(FRAME_SIZE);
+    #[allow(clippy::cast_possible_truncation)]
+    let textures = FruitTextures {
+        fruits: FruitKind::ALL
+            .into_iter()
+            .map(|kind| (kind, asset_server.load(asset_path(kind.texture()))))
+            .collect(),
+        layout: layouts.add(TextureAtlasLayout::from_grid(
+            frame_size,
+            FRAMES as u32,
+            1,
+            None,
+            None,
+        )),
+        collected: asset_server.load(asset_path("textures/collected.png")),
+        collected_layout: layouts.add(TextureAtlasLayout::from_grid(
+            frame_size,
+            COLLECTED_FRAMES as u32,
+            1,
+            None,
+            None,
+        )),
+    };
+
+    for texture in textures.fruits.values() {
+        loading.add(texture.clone());
+    }
+    loading.add(textures.collected.clone());
+    commands.insert_resource(textures);
+}
+
+pub fn spawn_fruit(
+    commands: &mut Commands,
+    definition: &FruitDefinition,
+    key: LevelKey,
+    collected: bool,
+) -> Entity {
+    let mut fruit = commands.spawn((*definition, key, definition.restart, LevelEntity));
+    if collected {
+        fruit.insert(Collected);
+    }
+    fruit.id()
+}
+
+/// Give fruit spawned by the level its sprite and hitbox.
+pub fn build_fruits(
+    mut commands: Commands,
+    fruits: Query<
+        (
+            Entity,
+            &FruitDefinition,
+            &LevelKey,
+            Has<Collected>,
+            Has<Boxed>,
+        ),
+        Added<FruitDefinition>,
+    >,
+    textures: Res<FruitTextures>,
+    collection: Res<FruitCollection>,
+    current: Res<CurrentLevel>,
+) {
+    let level = current.path();
+    for (entity, definition, key, collected, boxed) in &fruits {
+        let mut sprite = Sprite::from_atlas_image(
+            textures.fruits[&definition.kind].clone(),
+            TextureAtlas {
+                layout: textures.layout.clone(),
+                index: 0,
+            },
+        );
+        if collection.contains(&level, *key) {
+            sprite.color = SEEN_COLOR;
+        }
+
+        commands.entity(entity).insert((
+            sprite,
+            Transform {
+                translation: definition.position.extend(3.0),
+                scale: Vec3::splat(PIXEL_SCALE),
+                ..default()
+            },
+            if collected || boxed {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            },
+            LoopingAnimation::new(FRAMES, FRAME_SECONDS),
+            // The fruit is drawn small in the middle of its frame.
+            Collider {
+                size: Vec2::splat(12.0 * PIXEL_SCALE),
+                ..default()
+            },
+            Sensor,
+        ));
+    }
+}
+
+/// Collect fruit that characters touch.
+pub fn collect_fruits(
+    mut commands: Commands,
+    characters: Query<(&PhysicsPosition, &Collider), (With<Character>, Without<Dead>)>,
+    mut fruits: Query<
+        (
+            Entity,
+            &Transform,
+            &Collider,
+            &FruitDefinition,
+            &LevelKey,
+            &mut Sprite,
+            &mut LoopingAnimation,
+        ),
+        (Without<Collected>, Without<Boxed>),
+    >,
+    textures: Res<FruitTextures>,
+    mut state: ResMut<LevelState>,
+    mut progress: Progress,
+) {
+    for (entity, transform, collider, definition, key, mut sprite, mut animation) in &mut fruits {
+        let area = collider.aabb(transform.translation.truncate());
+        let touched = characters.iter().any(|(position, character_collider)| {
+            character_collider.aabb(position.current).intersects(&area)
+        });
+        if !touched {
+            continue;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let vanish_seconds = COLLECTED_FRAMES as f32 * FRAME_SECONDS;
+        commands.entity(entity).insert((
+            Collected,
+            Vanishing(Timer::from_seconds(vanish_seconds, TimerMode::Once)),
+        ));
+        *sprite = Sprite::from_atlas_image(
+            textures.collected.clone(),
+            TextureAtlas {
+                layout: textures.collected_layout.clone(),
+                index: 0,
+            },
+        );
+        animation.frames = COLLECTED_FRAMES;
+        animation.timer.reset();
+
+        state.clear(*key, definition.restart);
+        progress.collect(*key, definition.kind);
+    }
+}
+
+/// Hide fruit once its collected animation has played.
+pub fn vanish(
+    mut commands: Commands,
+    mut fruits: Query<(Entity, &mut Vanishing, &mut Visibility)>,
+    time: Res<Time>,
+) {
+    for (entity, mut timer, mut visibility) in &mut fruits {
+        if timer.tick(time.delta()).finished() {
+            *visibility = Visibility::Hidden;
+            commands.entity(entity).remove::<Vanishing>();
+        }
+    }
+}
+
+/// Write the [`FruitCollection`] to [`SAVE_PATH`] whenever a fruit is added
+/// to it.
+pub fn save_collection(collection: Res<FruitCollection>) {
+    if !collection.is_changed() || collection.is_added() {
+        return;
+    }
+
+    let save = match ron::ser::to_string_pretty(&*collection, PrettyConfig::default()) {
+        Ok(save) => save,
+        Err(err) => {
+            error!("Couldn't save the fruit collection: {err}");
+            return;
+        }
+    };
+    if let Err(err) = std::fs::write(SAVE_PATH, save) {
+        error!("Couldn't write {SAVE_PATH}: {err}");
+    }
+}
+
+pub fn count_fruits(
+    fruits: Query<Has<Collected>, With<FruitDefinition>>,
+    mut count: ResMut<FruitCount>,
+) {
+    count.set_if_neq(FruitCount {
+        collected: fruits.iter().filter(|collected| *collected).count(),
+        total: fruits.iter().count(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn_fruit_at(app: &mut App, position: Vec2, key: LevelKey) -> Entity {
+        let definition = FruitDefinition {
+            position,
+            kind: FruitKind::Cherries,
+            restart: RestartPolicy::Reset,
+        };
+        app.world_mut()
+            .spawn((
+                Transform::from_translation(position.extend(0.0)),
+                Collider::default(),
+                definition,
+                key,
+                Sprite::default(),
+                LoopingAnimation::new(FRAMES, FRAME_SECONDS),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn fruit_is_counted_and_only_scores_the_first_time() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(FruitTextures {
+                fruits: HashMap::default(),
+                layout: Handle::default(),
+                collected: Handle::default(),
+                collected_layout: Handle::default(),
+            })
+            .insert_resource(CurrentLevel(Handle::default()))
+            .init_resource::<LevelState>()
+            .init_resource::<FruitCollection>()
+            .init_resource::<FruitCount>()
+            .init_resource::<Score>()
+            .add_systems(Update, (collect_fruits, count_fruits).chain());
+
+        app.world_mut().spawn((
+            Character,
+            PhysicsPosition::new(Vec2::ZERO),
+            Collider::default(),
+        ));
+        let key = LevelKey("fruit", 0);
+        let fruit = spawn_fruit_at(&mut app, Vec2::ZERO, key);
+        spawn_fruit_at(&mut app, Vec2::new(500.0, 0.0), LevelKey("fruit", 1));
+
+        app.update();
+        assert_eq!(
+            *app.world().resource::<FruitCount>(),
+            FruitCount {
+                collected: 1,
+                total: 2,
+            }
+        );
+        assert_eq!(app.world().resource::<Score>().0, 2);
+        assert!(app.world().resource::<FruitCollection>().contains("", key));
+
+        // Once the level is reset, the same fruit is back to be collected
+        // again, but doesn't score again.
+        app.world_mut().despawn(fruit);
+        spawn_fruit_at(&mut app, Vec2::ZERO, key);
+        app.update();
+        assert_eq!(app.world().resource::<FruitCount>().collected, 1);
+        assert_eq!(app.world().resource::<Score>().0, 2);
+    }
+}
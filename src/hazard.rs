@@ -0,0 +1,405 @@
+//! Traps that hurt characters who touch them: spikes, saws and fire.
+//!
+//! What hurts is a trap's [`Collider`], not its sprite. The pack's sprites
+//! have room around the dangerous part, so the collider is usually smaller
+//! and offset to line up with it.
+
+use bevy::{
+    math::bounding::{BoundingVolume, IntersectsVolume},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    animation::LoopingAnimation,
+    assets::asset_path,
+    health::{Damage, Dead, Health},
+    loading::LoadingAssets,
+    physics::{self, Collider, PhysicsPosition, Sensor},
+    pixel_perfect::PIXEL_SCALE,
+    platform::Platform,
+};
+
+/// A trap, as written in a level file.
+#[derive(Component, Debug, Clone, Deserialize)]
+pub enum HazardDefinition {
+    /// A row of spikes standing on `position`.
+    Spikes {
+        position: Vec2,
+        #[serde(default = "default_count")]
+        count: u32,
+    },
+    /// A saw that goes from point to point along `path`, and from the last
+    /// point back to the first.
+    Saw {
+        path: Vec<Vec2>,
+        #[serde(default = "default_saw_speed")]
+        speed: f32,
+    },
+    /// A block standing on `position` that bursts into flames for
+    /// `on_seconds` at a time, with `off_seconds` between. `delay` holds off
+    /// the first burst, so that neighboring blocks can take turns.
+    Fire {
+        position: Vec2,
+        #[serde(default = "default_fire_seconds")]
+        on_seconds: f32,
+        #[serde(default = "default_fire_seconds")]
+        off_seconds: f32,
+        #[serde(default)]
+        delay: f32,
+    },
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+fn default_saw_speed() -> f32 {
+    100.0
+}
+
+fn default_fire_seconds() -> f32 {
+    2.0
+}
+
+/// Something that hurts characters who touch its [`HurtBox`], or its
+/// [`Collider`] if it doesn't have one.
+#[derive(Component)]
+pub struct Hazard {
+    pub damage: u32,
+    /// Whether it hurts right now.
+    pub armed: bool,
+}
+
+/// The part of a [`Hazard`] that hurts, for a hazard whose [`Collider`] is
+/// something else, such as a block that can be stood on.
+#[derive(Component)]
+pub struct HurtBox(pub Collider);
+
+/// Where a saw is headed.
+#[derive(Component)]
+pub struct SawPath {
+    points: Vec<Vec2>,
+    speed: f32,
+    next: usize,
+}
+
+#[derive(Component)]
+pub struct FireTrap {
+    phase: FirePhase,
+    timer: Timer,
+    on_seconds: f32,
+    off_seconds: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FirePhase {
+    Off,
+    /// Flickering, as a warning. It doesn't hurt yet.
+    Igniting,
+    On,
+}
+
+/// The traps' sprite sheets. Frames are square, except for fire's, which are
+/// twice as tall as they are wide.
+#[derive(Resource)]
+pub struct HazardTextures {
+    spikes: Handle<Image>,
+    saw: Handle<Image>,
+    saw_layout: Handle<TextureAtlasLayout>,
+    fire_off: Handle<Image>,
+    fire_igniting: Handle<Image>,
+    fire_igniting_layout: Handle<TextureAtlasLayout>,
+    fire_on: Handle<Image>,
+    fire_on_layout: Handle<TextureAtlasLayout>,
+}
+
+/// The size of a spike or a fire block, in pixels.
+const TILE: f32 = 16.0;
+const SAW_SIZE: u32 = 38;
+const SAW_FRAMES: usize = 8;
+const FIRE_IGNITING_FRAMES: usize = 4;
+const FIRE_ON_FRAMES: usize = 3;
+const FRAME_SECONDS: f32 = 0.05;
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut loading: ResMut<LoadingAssets>,
+) {
+    let fire_frame = UVec2::new(16, 32);
+    #[allow(clippy::cast_possible_truncation)]
+    let textures = HazardTextures {
+        spikes: asset_server.load(asset_path("textures/spikes.png")),
+        saw: asset_server.load(asset_path("textures/saw.png")),
+        saw_layout: layouts.add(TextureAtlasLayout::from_grid(
+            UVec2::splat(SAW_SIZE),
+            SAW_FRAMES as u32,
+            1,
+            None,
+            None,
+        )),
+        fire_off: asset_server.load(asset_path("textures/fire_off.png")),
+        fire_igniting: asset_server.load(asset_path("textures/fire_hit.png")),
+        fire_igniting_layout: layouts.add(TextureAtlasLayout::from_grid(
+            fire_frame,
+            FIRE_IGNITING_FRAMES as u32,
+            1,
+            None,
+            None,
+        )),
+        fire_on: asset_server.load(asset_path("textures/fire_on.png")),
+        fire_on_layout: layouts.add(TextureAtlasLayout::from_grid(
+            fire_frame,
+            FIRE_ON_FRAMES as u32,
+            1,
+            None,
+            None,
+        )),
+    };
+
+    loading.add(textures.spikes.clone());
+    loading.add(textures.saw.clone());
+    loading.add(textures.fire_off.clone());
+    loading.add(textures.fire_igniting.clone());
+    loading.add(textures.fire_on.clone());
+    commands.insert_resource(textures);
+}
+
+/// Give traps spawned by the level their sprites and hitboxes.
+pub fn build_hazards(
+    mut commands: Commands,
+    hazards: Query<(Entity, &HazardDefinition), Added<HazardDefinition>>,
+    textures: Res<HazardTextures>,
+) {
+    for (entity, definition) in &hazards {
+        match definition {
+            HazardDefinition::Spikes { position, count } => {
+                commands
+                    .entity(entity)
+                    .insert(spikes(&textures, *position, *count));
+            }
+            HazardDefinition::Saw { path, speed } => {
+                commands
+                    .entity(entity)
+                    .insert(saw(&textures, path.clone(), *speed));
+            }
+            HazardDefinition::Fire {
+                position,
+                on_seconds,
+                off_seconds,
+                delay,
+            } => {
+                let timer = Timer::from_seconds(off_seconds + delay, TimerMode::Once);
+                commands.entity(entity).insert(fire(
+                    &textures,
+                    *position,
+                    FireTrap {
+                        phase: FirePhase::Off,
+                        timer,
+                        on_seconds: *on_seconds,
+                        off_seconds: *off_seconds,
+                    },
+                ));
+            }
+        }
+    }
+}
+
+fn spikes(textures: &HazardTextures, position: Vec2, count: u32) -> impl Bundle {
+    #[allow(clippy::cast_precision_loss)]
+    let width = TILE * count as f32;
+    let mut sprite = Sprite::from_image(textures.spikes.clone());
+    sprite.custom_size = Some(Vec2::new(width, TILE));
+    sprite.image_mode = SpriteImageMode::Tiled {
+        tile_x: true,
+        tile_y: false,
+        stretch_value: 1.0,
+    };
+
+    (
+        sprite,
+        Transform {
+            translation: (position + Vec2::Y * TILE / 2.0 * PIXEL_SCALE).extend(2.0),
+            scale: Vec3::splat(PIXEL_SCALE),
+            ..default()
+        },
+        // The spikes take up the bottom half of the tile, but their tips are
+        // too thin to count.
+        Collider {
+            size: Vec2::new(width - 2.0, 6.0) * PIXEL_SCALE,
+            offset: Vec2::new(0.0, -5.0) * PIXEL_SCALE,
+        },
+        Sensor,
+        Hazard {
+            damage: 1,
+            armed: true,
+        },
+    )
+}
+
+fn saw(textures: &HazardTextures, path: Vec<Vec2>, speed: f32) -> impl Bundle {
+    let start = path.first().copied().unwrap_or_default();
+    (
+        Sprite::from_atlas_image(
+            textures.saw.clone(),
+            TextureAtlas {
+                layout: textures.saw_layout.clone(),
+                index: 0,
+            },
+        ),
+        Transform {
+            translation: start.extend(2.0),
+            scale: Vec3::splat(PIXEL_SCALE),
+            ..default()
+        },
+        LoopingAnimation::new(SAW_FRAMES, FRAME_SECONDS),
+        PhysicsPosition::new(start),
+        SawPath {
+            points: path,
+            speed,
+            next: 0,
+        },
+        // A box that fits inside the round blade, so that brushing a corner
+        // doesn't hurt.
+        Collider {
+            size: Vec2::splat(26.0 * PIXEL_SCALE),
+            ..default()
+        },
+        Sensor,
+        Hazard {
+            damage: 1,
+            armed: true,
+        },
+    )
+}
+
+fn fire(textures: &HazardTextures, position: Vec2, trap: FireTrap) -> impl Bundle {
+    (
+        Sprite::from_image(textures.fire_off.clone()),
+        Transform {
+            translation: (position + Vec2::Y * TILE * PIXEL_SCALE).extend(2.0),
+            scale: Vec3::splat(PIXEL_SCALE),
+            ..default()
+        },
+        LoopingAnimation::new(1, FRAME_SECONDS * 2.0),
+        trap,
+        // The block in the bottom half can be stood on, and only the flames,
+        // in the top half, hurt.
+        Collider {
+            size: Vec2::splat(TILE) * PIXEL_SCALE,
+            offset: Vec2::new(0.0, -TILE / 2.0) * PIXEL_SCALE,
+        },
+        Platform,
+        HurtBox(Collider {
+            size: Vec2::new(10.0, 12.0) * PIXEL_SCALE,
+            offset: Vec2::new(0.0, 6.0) * PIXEL_SCALE,
+        }),
+        Hazard {
+            damage: 1,
+            armed: false,
+        },
+    )
+}
+
+/// Move saws along their paths.
+pub fn move_saws(mut saws: Query<(&mut SawPath, &mut PhysicsPosition)>, time: Res<Time>) {
+    for (mut saw, mut position) in &mut saws {
+        position.previous = position.current;
+        let Some(&target) = saw.points.get(saw.next) else {
+            continue;
+        };
+
+        let step = saw.speed * time.delta_secs();
+        let remaining = target - position.current;
+        if remaining.length() > step {
+            position.current += remaining.normalize() * step;
+        } else {
+            position.current = target;
+            saw.next = (saw.next + 1) % saw.points.len();
+        }
+    }
+}
+
+/// Turn fire traps on and off, and only let them hurt while they're on.
+pub fn cycle_fire(
+    mut fires: Query<(
+        &mut FireTrap,
+        &mut Hazard,
+        &mut Sprite,
+        &mut LoopingAnimation,
+    )>,
+    textures: Res<HazardTextures>,
+    time: Res<Time>,
+) {
+    for (mut fire, mut hazard, mut sprite, mut animation) in &mut fires {
+        if !fire.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let (phase, seconds) = match fire.phase {
+            FirePhase::Off => (
+                FirePhase::Igniting,
+                FIRE_IGNITING_FRAMES as f32 * animation.timer.duration().as_secs_f32(),
+            ),
+            FirePhase::Igniting => (FirePhase::On, fire.on_seconds),
+            FirePhase::On => (FirePhase::Off, fire.off_seconds),
+        };
+        fire.phase = phase;
+        fire.timer = Timer::from_seconds(seconds, TimerMode::Once);
+        hazard.armed = phase == FirePhase::On;
+
+        let (image, atlas, frames) = match phase {
+            FirePhase::Off => (textures.fire_off.clone(), None, 1),
+            FirePhase::Igniting => (
+                textures.fire_igniting.clone(),
+                Some(textures.fire_igniting_layout.clone()),
+                FIRE_IGNITING_FRAMES,
+            ),
+            FirePhase::On => (
+                textures.fire_on.clone(),
+                Some(textures.fire_on_layout.clone()),
+                FIRE_ON_FRAMES,
+            ),
+        };
+        sprite.image = image;
+        sprite.texture_atlas = atlas.map(|layout| TextureAtlas { layout, index: 0 });
+        animation.frames = frames;
+        animation.timer.reset();
+    }
+}
+
+/// Hurt characters who touch an armed trap, knocking them away from it.
+///
+/// Moving traps, such as saws, are checked where the simulation has them
+/// rather than where they were last drawn.
+pub fn hurt_on_contact(
+    characters: Query<(Entity, &PhysicsPosition, &Collider), (With<Health>, Without<Dead>)>,
+    hazards: Query<(
+        &Hazard,
+        &Transform,
+        Option<&PhysicsPosition>,
+        &Collider,
+        Option<&HurtBox>,
+    )>,
+    mut damage: EventWriter<Damage>,
+) {
+    for (hazard, transform, position, collider, hurt_box) in &hazards {
+        if !hazard.armed {
+            continue;
+        }
+        let hurts = hurt_box.map_or(collider, |hurt_box| &hurt_box.0);
+        let area = hurts.aabb(physics::simulated_position(transform, position));
+        for (entity, position, character_collider) in &characters {
+            if character_collider.aabb(position.current).intersects(&area) {
+                damage.send(Damage {
+                    target: entity,
+                    amount: hazard.damage,
+                    source: area.center(),
+                });
+            }
+        }
+    }
+}
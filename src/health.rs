@@ -0,0 +1,209 @@
+//! Characters getting hurt, and dying.
+//!
+//! Anything that hurts a character sends a [`Damage`] event rather than
+//! touching its [`Health`], so that invulnerability and knockback are handled
+//! the same way whatever did the hurting.
+
+use bevy::prelude::*;
+
+use crate::{
+    camera_effects::CameraEffect,
+    character::{self, CharacterAnimations, CharacterState},
+    checkpoint::RespawnPoint,
+    input::Player,
+    physics::{Grounded, PhysicsPosition, Velocity},
+    restart::{ResetCause, ResetLevel},
+};
+
+/// How many hits a character can take.
+pub const MAX_HEALTH: u32 = 3;
+
+/// How long a character can't be hurt again after being hurt.
+const INVULNERABLE_SECONDS: f32 = 1.5;
+
+/// How long a character has no control after being hurt.
+const HURT_SECONDS: f32 = 0.4;
+
+/// How long after dying a character respawns.
+const DEATH_SECONDS: f32 = 1.5;
+
+/// How hard a character is knocked away from what hurt it.
+const KNOCKBACK: Vec2 = Vec2::new(250.0, 350.0);
+
+/// How many more hits a character can take.
+#[derive(Component, Debug)]
+pub struct Health(pub u32);
+
+/// Hurt a character.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: u32,
+    /// Where the damage came from, in the world. The character is knocked
+    /// away from here.
+    pub source: Vec2,
+}
+
+/// Kill a character outright, even if it can't be hurt right now.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Kill {
+    pub target: Entity,
+}
+
+/// A character that was hurt recently and can't be hurt again until this runs
+/// out.
+#[derive(Component, Deref, DerefMut)]
+pub struct Invulnerable(Timer);
+
+/// A character that is reeling from a hit and can't be controlled until this
+/// runs out. A dead character is hurt until it respawns.
+#[derive(Component, Deref, DerefMut)]
+pub struct Hurt(Timer);
+
+/// A character that has run out of health, and will respawn when this runs
+/// out.
+#[derive(Component, Deref, DerefMut)]
+pub struct Dead(Timer);
+
+/// Take health away from characters, knock them back, and kill them if
+/// they've run out.
+pub fn apply_damage(
+    mut commands: Commands,
+    mut damages: EventReader<Damage>,
+    mut kills: EventReader<Kill>,
+    mut characters: Query<
+        (
+            &mut Health,
+            &mut Velocity,
+            &mut CharacterState,
+            &PhysicsPosition,
+            Has<Invulnerable>,
+        ),
+        Without<Dead>,
+    >,
+    mut camera_effects: EventWriter<CameraEffect>,
+) {
+    for damage in damages.read() {
+        let Ok((mut health, mut velocity, mut state, position, invulnerable)) =
+            characters.get_mut(damage.target)
+        else {
+            continue;
+        };
+        if invulnerable || health.0 == 0 {
+            continue;
+        }
+
+        health.0 = health.0.saturating_sub(damage.amount);
+        *state = CharacterState::Hit;
+        camera_effects.send(CameraEffect::Shake(0.3));
+
+        if health.0 == 0 {
+            die(&mut commands, damage.target, &mut velocity);
+            continue;
+        }
+
+        let away = if position.current.x < damage.source.x {
+            -1.0
+        } else {
+            1.0
+        };
+        velocity.x = KNOCKBACK.x * away;
+        velocity.y = KNOCKBACK.y;
+        velocity.is_grounded = false;
+        commands.entity(damage.target).insert((
+            Invulnerable(Timer::from_seconds(INVULNERABLE_SECONDS, TimerMode::Once)),
+            Hurt(Timer::from_seconds(HURT_SECONDS, TimerMode::Once)),
+        ));
+    }
+
+    for kill in kills.read() {
+        let Ok((mut health, mut velocity, mut state, ..)) = characters.get_mut(kill.target) else {
+            continue;
+        };
+        if health.0 == 0 {
+            continue;
+        }
+
+        health.0 = 0;
+        *state = CharacterState::Hit;
+        die(&mut commands, kill.target, &mut velocity);
+    }
+}
+
+/// Start a character's death sequence. It's thrown up into the air and falls
+/// through the level, like in the classics.
+fn die(commands: &mut Commands, character: Entity, velocity: &mut Velocity) {
+    velocity.x = 0.0;
+    velocity.y = KNOCKBACK.y * 1.5;
+    velocity.is_grounded = false;
+    commands.entity(character).remove::<Grounded>().insert((
+        Dead(Timer::from_seconds(DEATH_SECONDS, TimerMode::Once)),
+        Hurt(Timer::from_seconds(DEATH_SECONDS, TimerMode::Once)),
+    ));
+}
+
+/// Wear off being hurt and invulnerable.
+pub fn recover(
+    mut commands: Commands,
+    mut invulnerable: Query<(Entity, &mut Invulnerable)>,
+    mut hurt: Query<(Entity, &mut Hurt)>,
+    time: Res<Time>,
+) {
+    for (entity, mut timer) in &mut invulnerable {
+        if timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+    for (entity, mut timer) in &mut hurt {
+        if timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Hurt>();
+        }
+    }
+}
+
+/// Replace each dead character with a fresh one at the [`RespawnPoint`] once
+/// it has finished dying.
+///
+/// The level is only reset for a death once nobody is left alive in it, so
+/// that in co-op, one player dying doesn't undo the level under everyone who
+/// is still playing.
+pub fn respawn_dead(
+    mut commands: Commands,
+    mut dead: Query<(Entity, &mut Dead, &Player)>,
+    living: Query<(), (With<Health>, Without<Dead>)>,
+    animations: Option<Res<CharacterAnimations>>,
+    respawn: Option<Res<RespawnPoint>>,
+    mut reset: EventWriter<ResetLevel>,
+    time: Res<Time>,
+) {
+    let (Some(animations), Some(respawn)) = (animations, respawn) else {
+        return;
+    };
+
+    let mut respawned = false;
+    for (entity, mut timer, player) in &mut dead {
+        if timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            character::spawn_character(
+                &mut commands,
+                &animations,
+                *player,
+                respawn.for_player(*player),
+            );
+            respawned = true;
+        }
+    }
+    if respawned && living.is_empty() {
+        reset.send(ResetLevel(ResetCause::Death));
+    }
+}
+
+/// Flash invulnerable characters so that it's clear they can't be hurt.
+pub fn blink_invulnerable(
+    mut characters: Query<(&mut Sprite, Option<&Invulnerable>), With<Health>>,
+) {
+    for (mut sprite, invulnerable) in &mut characters {
+        let visible = invulnerable.is_none_or(|timer| timer.elapsed_secs() * 10.0 % 2.0 < 1.0);
+        sprite.color.set_alpha(if visible { 1.0 } else { 0.3 });
+    }
+}
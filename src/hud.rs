@@ -0,0 +1,48 @@
+//! What's shown over the level while it's being played.
+
+use bevy::prelude::*;
+
+use crate::{
+    fruit::{FruitCount, Score},
+    loading::GameState,
+};
+
+/// The text showing how much fruit has been collected.
+#[derive(Component)]
+pub struct FruitCounter;
+
+pub fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                left: Val::Px(12.0),
+                ..default()
+            },
+            StateScoped(GameState::Playing),
+        ))
+        .with_child((
+            Text::default(),
+            TextFont::from_font_size(20.0),
+            TextColor(Color::WHITE),
+            FruitCounter,
+        ));
+}
+
+pub fn update_fruit_counter(
+    count: Res<FruitCount>,
+    score: Res<Score>,
+    mut counter: Query<&mut Text, With<FruitCounter>>,
+) {
+    if !count.is_changed() && !score.is_changed() {
+        return;
+    }
+
+    for mut text in &mut counter {
+        text.0 = format!(
+            "Fruit {}/{}   Score {}",
+            count.collected, count.total, score.0
+        );
+    }
+}
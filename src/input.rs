@@ -0,0 +1,261 @@
+//! Player input, gathered from the keyboard and any connected gamepads.
+//!
+//! Each player owns a [`PlayerInput`], which is their keyboard layout and/or
+//! gamepad. Players are kept in the [`Roster`] so that they survive a restart,
+//! which despawns and respawns their characters.
+
+use bevy::{
+    input::gamepad::{GamepadConnection, GamepadConnectionEvent},
+    prelude::*,
+};
+
+use crate::{
+    character::{self, CharacterAnimations},
+    checkpoint::RespawnPoint,
+};
+
+/// Stick deflection below this is treated as the stick being centered.
+const STICK_DEADZONE: f32 = 0.2;
+
+/// Stick deflection above this is treated as the stick being fully pushed.
+const STICK_LIVEZONE: f32 = 0.9;
+
+/// The most players we have room for on the couch.
+pub const MAX_PLAYERS: usize = 4;
+
+/// Which keys a keyboard player uses. Two players can share a keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardLayout {
+    Arrows,
+    Wasd,
+}
+
+impl KeyboardLayout {
+    fn left(self) -> KeyCode {
+        match self {
+            KeyboardLayout::Arrows => KeyCode::ArrowLeft,
+            KeyboardLayout::Wasd => KeyCode::KeyA,
+        }
+    }
+
+    fn right(self) -> KeyCode {
+        match self {
+            KeyboardLayout::Arrows => KeyCode::ArrowRight,
+            KeyboardLayout::Wasd => KeyCode::KeyD,
+        }
+    }
+
+    fn jump(self) -> KeyCode {
+        match self {
+            KeyboardLayout::Arrows => KeyCode::Space,
+            KeyboardLayout::Wasd => KeyCode::KeyW,
+        }
+    }
+}
+
+/// Where a single player's input comes from.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerInput {
+    pub keyboard: Option<KeyboardLayout>,
+    pub gamepad: Option<Entity>,
+}
+
+impl PlayerInput {
+    /// How far left (negative) or right (positive) the player wants to move,
+    /// in the range -1.0 to 1.0.
+    ///
+    /// Keys and the D-pad are digital and always give full strength; the left
+    /// stick gives anything in between so that a light push walks slowly.
+    pub fn horizontal_axis(
+        &self,
+        keyboard_input: &ButtonInput<KeyCode>,
+        gamepads: &Query<&Gamepad>,
+    ) -> f32 {
+        let mut axis: f32 = 0.0;
+
+        if let Some(layout) = self.keyboard {
+            if keyboard_input.pressed(layout.left()) {
+                axis -= 1.0;
+            }
+            if keyboard_input.pressed(layout.right()) {
+                axis += 1.0;
+            }
+        }
+
+        if let Some(gamepad) = self.gamepad.and_then(|entity| gamepads.get(entity).ok()) {
+            if gamepad.pressed(GamepadButton::DPadLeft) {
+                axis -= 1.0;
+            }
+            if gamepad.pressed(GamepadButton::DPadRight) {
+                axis += 1.0;
+            }
+            axis += apply_deadzone(gamepad.left_stick().x);
+        }
+
+        axis.clamp(-1.0, 1.0)
+    }
+
+    /// Did the player press jump this frame?
+    pub fn jump_just_pressed(
+        &self,
+        keyboard_input: &ButtonInput<KeyCode>,
+        gamepads: &Query<&Gamepad>,
+    ) -> bool {
+        let keyboard = self
+            .keyboard
+            .is_some_and(|layout| keyboard_input.just_pressed(layout.jump()));
+        let gamepad = self
+            .gamepad
+            .and_then(|entity| gamepads.get(entity).ok())
+            .is_some_and(|gamepad| gamepad.just_pressed(GamepadButton::South));
+
+        keyboard || gamepad
+    }
+}
+
+/// Everyone who has joined the game, indexed by [`Player`].
+#[derive(Resource)]
+pub struct Roster(pub Vec<PlayerInput>);
+
+impl Default for Roster {
+    /// Player one starts out on the arrow keys. Everyone else has to join.
+    fn default() -> Self {
+        Roster(vec![PlayerInput {
+            keyboard: Some(KeyboardLayout::Arrows),
+            gamepad: None,
+        }])
+    }
+}
+
+impl Roster {
+    fn has_gamepad(&self, gamepad: Entity) -> bool {
+        self.0.iter().any(|input| input.gamepad == Some(gamepad))
+    }
+
+    fn has_keyboard(&self, layout: KeyboardLayout) -> bool {
+        self.0.iter().any(|input| input.keyboard == Some(layout))
+    }
+}
+
+/// Which player a character belongs to, as an index into the [`Roster`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Player(pub usize);
+
+/// Rescale a raw stick value so that it starts at 0.0 at the edge of the
+/// deadzone and reaches 1.0 at the livezone, rather than jumping straight
+/// from 0.0 to 0.2 as the stick leaves the deadzone.
+fn apply_deadzone(value: f32) -> f32 {
+    let magnitude = value.abs();
+    if magnitude < STICK_DEADZONE {
+        return 0.0;
+    }
+
+    let scaled = (magnitude - STICK_DEADZONE) / (STICK_LIVEZONE - STICK_DEADZONE);
+    scaled.min(1.0).copysign(value)
+}
+
+/// Configure gamepads as they are plugged in, and let go of them when they
+/// are unplugged so that they can be picked up again on reconnect.
+///
+/// Bevy filters axes through its own small deadzone before we ever see them,
+/// which would leave a step at the edge of ours. Turn it off so that
+/// [`apply_deadzone`] does all the work.
+pub fn handle_gamepad_connections(
+    mut connection_events: EventReader<GamepadConnectionEvent>,
+    mut settings: Query<&mut GamepadSettings>,
+    mut roster: ResMut<Roster>,
+) {
+    for event in connection_events.read() {
+        if let GamepadConnection::Disconnected = event.connection {
+            for input in &mut roster.0 {
+                if input.gamepad == Some(event.gamepad) {
+                    input.gamepad = None;
+                }
+            }
+            continue;
+        }
+
+        let Ok(mut settings) = settings.get_mut(event.gamepad) else {
+            continue;
+        };
+
+        let axis_settings = &mut settings.default_axis_settings;
+        axis_settings.set_deadzone_lowerbound(0.0);
+        axis_settings.set_deadzone_upperbound(0.0);
+    }
+}
+
+/// Let more players join in.
+///
+/// - Pressing jump on a gamepad nobody is using hands it to the first player
+///   without one, so that a single player can pick up a controller.
+/// - Pressing start on a gamepad nobody is using adds a new player.
+/// - Pressing the WASD jump key adds a new player on that half of the keyboard.
+pub fn join_players(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<(Entity, &Gamepad)>,
+    mut roster: ResMut<Roster>,
+    animations: Option<Res<CharacterAnimations>>,
+    respawn: Option<Res<RespawnPoint>>,
+) {
+    // Nobody can join until there's a character to give them, and somewhere
+    // to put it.
+    let (Some(animations), Some(respawn)) = (animations, respawn) else {
+        return;
+    };
+
+    for (entity, gamepad) in &gamepads {
+        if roster.has_gamepad(entity) {
+            continue;
+        }
+
+        if gamepad.just_pressed(GamepadButton::South) {
+            if let Some(input) = roster.0.iter_mut().find(|input| input.gamepad.is_none()) {
+                input.gamepad = Some(entity);
+            }
+        } else if gamepad.just_pressed(GamepadButton::Start) {
+            add_player(
+                &mut commands,
+                &mut roster,
+                &animations,
+                &respawn,
+                PlayerInput {
+                    keyboard: None,
+                    gamepad: Some(entity),
+                },
+            );
+        }
+    }
+
+    let wasd = KeyboardLayout::Wasd;
+    if keyboard_input.just_pressed(wasd.jump()) && !roster.has_keyboard(wasd) {
+        add_player(
+            &mut commands,
+            &mut roster,
+            &animations,
+            &respawn,
+            PlayerInput {
+                keyboard: Some(wasd),
+                gamepad: None,
+            },
+        );
+    }
+}
+
+fn add_player(
+    commands: &mut Commands,
+    roster: &mut Roster,
+    animations: &CharacterAnimations,
+    respawn: &RespawnPoint,
+    input: PlayerInput,
+) {
+    if roster.0.len() >= MAX_PLAYERS {
+        return;
+    }
+
+    let player = Player(roster.0.len());
+    roster.0.push(input);
+    info!("Player {} joined.", player.0 + 1);
+    character::spawn_character(commands, animations, player, respawn.for_player(player));
+}
@@ -0,0 +1,356 @@
+//! Levels, which are loaded from `.level.ron` files under `assets/levels`.
+//!
+//! Everything a level spawns is marked with [`LevelEntity`], so that when the
+//! file changes, or the level is restarted, the whole level can be torn down
+//! and spawned again. The characters aren't part of the level, so they stay
+//! where they are.
+//!
+//! Whatever has been done in the level, such as reaching checkpoints, is kept
+//! in the [`LevelState`] so that it survives the level being spawned again. A
+//! [`ResetLevel`] undoes some of it first, depending on each thing's
+//! [`RestartPolicy`].
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use serde::Deserialize;
+
+use crate::{
+    assets::asset_path,
+    background::{BackgroundLayer, ParallaxBackground},
+    block::{self, BlockDefinition},
+    camera_zones::{self, CameraZone},
+    checkpoint::{self, CheckpointDefinition, RespawnPoint},
+    enemy::{self, EnemyDefinition},
+    fruit::{self, FruitDefinition},
+    hazard::HazardDefinition,
+    health::{Dead, Health, Kill},
+    loading::LoadingAssets,
+    physics::PhysicsPosition,
+    restart::{ResetCause, ResetLevel, RestartPolicy},
+    tilemap::TileMap,
+};
+
+/// The part of the world the level takes up. The camera won't show anything
+/// outside of it.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct LevelBounds(pub Rect);
+
+/// Characters die when they fall below this height.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct KillHeight(pub f32);
+
+/// A level, as written in its file.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct LevelDefinition {
+    /// Where the characters start.
+    #[serde(default)]
+    pub spawn: Vec2,
+    /// Characters die when they leave the bounds to either side.
+    pub bounds: Rect,
+    /// Defaults to the bottom of the bounds.
+    #[serde(default)]
+    pub kill_height: Option<f32>,
+    /// The background layers, furthest away first.
+    #[serde(default)]
+    pub background: Vec<BackgroundLayer>,
+    #[serde(default)]
+    pub terrain: Option<TileMap>,
+    #[serde(default)]
+    pub camera_zones: Vec<CameraZone>,
+    #[serde(default)]
+    pub checkpoints: Vec<CheckpointDefinition>,
+    #[serde(default)]
+    pub hazards: Vec<HazardDefinition>,
+    #[serde(default)]
+    pub fruits: Vec<FruitDefinition>,
+    #[serde(default)]
+    pub enemies: Vec<EnemyDefinition>,
+    #[serde(default)]
+    pub blocks: Vec<BlockDefinition>,
+}
+
+/// The level being played.
+#[derive(Resource)]
+pub struct CurrentLevel(pub Handle<LevelDefinition>);
+
+impl CurrentLevel {
+    /// The level's path under `assets/`, which, unlike its handle, stays the
+    /// same from one run to the next.
+    pub fn path(&self) -> String {
+        self.0
+            .path()
+            .map(|path| path.path().to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Something that was spawned from the [`CurrentLevel`]'s definition.
+#[derive(Component)]
+pub struct LevelEntity;
+
+/// Which of the things in a level's definition a [`LevelEntity`] was spawned
+/// from, by kind and index, e.g. `("checkpoint", 0)`.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LevelKey(pub &'static str, pub usize);
+
+/// What has been done in the level so far.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct LevelState {
+    /// Things that have been dealt with, such as reached checkpoints, and
+    /// what should happen to them when the level is reset.
+    cleared: HashMap<LevelKey, RestartPolicy>,
+}
+
+impl LevelState {
+    pub fn clear(&mut self, key: LevelKey, policy: RestartPolicy) {
+        self.cleared.insert(key, policy);
+    }
+
+    pub fn is_cleared(&self, key: LevelKey) -> bool {
+        self.cleared.contains_key(&key)
+    }
+
+    /// Undo whatever was done since the snapshot to things that reset for this
+    /// cause. Without a snapshot, they go back to how they were before the
+    /// level was played.
+    pub fn reset(&mut self, snapshot: Option<&LevelState>, cause: ResetCause) {
+        self.cleared.retain(|key, policy| {
+            !policy.resets_on(cause) || snapshot.is_some_and(|snapshot| snapshot.is_cleared(*key))
+        });
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut loading: ResMut<LoadingAssets>,
+) {
+    let level = asset_server.load(asset_path("levels/level1.level.ron"));
+    loading.add(level.clone());
+    commands.insert_resource(CurrentLevel(level));
+}
+
+/// The [`CurrentLevel`]'s definition, and whether it has just loaded or
+/// changed.
+#[derive(SystemParam)]
+pub struct LevelSource<'w, 's> {
+    events: EventReader<'w, 's, AssetEvent<LevelDefinition>>,
+    current: Res<'w, CurrentLevel>,
+    levels: Res<'w, Assets<LevelDefinition>>,
+}
+
+impl LevelSource<'_, '_> {
+    /// Whether the level has loaded or its file changed since the last call.
+    fn changed(&mut self) -> bool {
+        let current = self.current.0.id();
+        self.events
+            .read()
+            .any(|event| event.is_loaded_with_dependencies(current) || event.is_modified(current))
+    }
+
+    fn get(&self) -> Option<&LevelDefinition> {
+        self.levels.get(&self.current.0)
+    }
+}
+
+/// Spawn the level once it has loaded, and again whenever its file changes or
+/// it's restarted.
+///
+/// When everybody has died, only what resets on death is despawned and
+/// spawned again, and the rest of the level stays as it is.
+pub fn spawn_level(
+    mut commands: Commands,
+    mut source: LevelSource,
+    mut resets: EventReader<ResetLevel>,
+    mut state: ResMut<LevelState>,
+    mut respawn: Option<ResMut<RespawnPoint>>,
+    spawned: Query<(Entity, Option<&RestartPolicy>), With<LevelEntity>>,
+) {
+    let mut rebuild = source.changed();
+    let mut died = false;
+    for ResetLevel(cause) in resets.read() {
+        rebuild |= cause.is_restart();
+        died |= *cause == ResetCause::Death;
+        let Some(respawn) = respawn.as_deref_mut() else {
+            continue;
+        };
+        if *cause == ResetCause::RestartFromStart {
+            *respawn = RespawnPoint::new(respawn.start);
+        }
+        state.reset(respawn.snapshot.as_ref(), *cause);
+    }
+    if !rebuild && !died {
+        return;
+    }
+    let Some(level) = source.get() else {
+        return;
+    };
+
+    // Things without a policy, such as the terrain, are never reset on their
+    // own.
+    let respawns = |policy: Option<RestartPolicy>| {
+        rebuild || policy.is_some_and(|policy| policy.resets_on(ResetCause::Death))
+    };
+    for (entity, policy) in &spawned {
+        if respawns(policy.copied()) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    if rebuild {
+        commands.insert_resource(LevelBounds(level.bounds));
+        commands.insert_resource(KillHeight(level.kill_height.unwrap_or(level.bounds.min.y)));
+        match respawn {
+            Some(mut respawn) => respawn.start = level.spawn,
+            None => commands.insert_resource(RespawnPoint::new(level.spawn)),
+        }
+
+        commands.spawn((ParallaxBackground(level.background.clone()), LevelEntity));
+
+        if let Some(terrain) = &level.terrain {
+            commands.spawn((terrain.clone(), LevelEntity));
+        }
+
+        for hazard in &level.hazards {
+            commands.spawn((hazard.clone(), LevelEntity));
+        }
+
+        for (index, zone) in level.camera_zones.iter().enumerate() {
+            let key = LevelKey("camera zone", index);
+            camera_zones::spawn_camera_zone(&mut commands, zone, key, state.is_cleared(key));
+        }
+    }
+
+    for (index, checkpoint) in level.checkpoints.iter().enumerate() {
+        if respawns(Some(checkpoint.restart)) {
+            let key = LevelKey("checkpoint", index);
+            checkpoint::spawn_checkpoint(&mut commands, checkpoint, key, state.is_cleared(key));
+        }
+    }
+
+    for (index, fruit) in level.fruits.iter().enumerate() {
+        if respawns(Some(fruit.restart)) {
+            let key = LevelKey("fruit", index);
+            fruit::spawn_fruit(&mut commands, fruit, key, state.is_cleared(key));
+        }
+    }
+
+    for (index, enemy) in level.enemies.iter().enumerate() {
+        if respawns(Some(enemy.restart)) {
+            let key = LevelKey("enemy", index);
+            enemy::spawn_enemy(&mut commands, enemy, key, state.is_cleared(key));
+        }
+    }
+
+    for (index, block) in level.blocks.iter().enumerate() {
+        if respawns(block.restart()) {
+            block::spawn_block(&mut commands, block, index, &state);
+        }
+    }
+}
+
+/// Kill characters that have fallen out of the level or left it to either
+/// side. Leaving through the top is fine, since they'll fall back in.
+pub fn kill_out_of_bounds(
+    characters: Query<(Entity, &PhysicsPosition), (With<Health>, Without<Dead>)>,
+    bounds: Option<Res<LevelBounds>>,
+    kill_height: Option<Res<KillHeight>>,
+    mut kills: EventWriter<Kill>,
+) {
+    let (Some(bounds), Some(kill_height)) = (bounds, kill_height) else {
+        return;
+    };
+
+    for (entity, position) in &characters {
+        let position = position.current;
+        if position.y < kill_height.0 || position.x < bounds.0.min.x || position.x > bounds.0.max.x
+        {
+            kills.send(Kill { target: entity });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resets_follow_each_policy() {
+        let checkpoint = LevelKey("checkpoint", 0);
+        let reset = LevelKey("reset", 0);
+        let on_death = LevelKey("on death", 0);
+        let before_snapshot = LevelKey("reset", 1);
+
+        let mut snapshot = LevelState::default();
+        snapshot.clear(before_snapshot, RestartPolicy::Reset);
+        let mut played = snapshot.clone();
+        played.clear(checkpoint, RestartPolicy::Persist);
+        played.clear(reset, RestartPolicy::Reset);
+        played.clear(on_death, RestartPolicy::ResetOnDeath);
+
+        let mut state = played.clone();
+        state.reset(Some(&snapshot), ResetCause::Restart);
+        assert!(state.is_cleared(checkpoint));
+        assert!(!state.is_cleared(reset));
+        assert!(state.is_cleared(on_death));
+        assert!(state.is_cleared(before_snapshot));
+
+        let mut state = played.clone();
+        state.reset(Some(&snapshot), ResetCause::Death);
+        assert!(state.is_cleared(checkpoint));
+        assert!(!state.is_cleared(on_death));
+        assert!(state.is_cleared(before_snapshot));
+
+        let mut state = played;
+        state.reset(None, ResetCause::RestartFromStart);
+        assert!(state.cleared.is_empty());
+    }
+
+    #[test]
+    fn a_fallen_platform_is_back_after_a_death() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Assets<LevelDefinition>>()
+            .init_resource::<LevelState>()
+            .add_event::<AssetEvent<LevelDefinition>>()
+            .add_event::<ResetLevel>()
+            .add_systems(Update, spawn_level);
+
+        let level: LevelDefinition = ron::from_str(
+            "(
+                bounds: (min: (-500.0, -500.0), max: (500.0, 500.0)),
+                blocks: [Falling(position: (0.0, 0.0))],
+            )",
+        )
+        .unwrap();
+        let level = app
+            .world_mut()
+            .resource_mut::<Assets<LevelDefinition>>()
+            .add(level);
+        app.world_mut()
+            .send_event(AssetEvent::LoadedWithDependencies { id: level.id() });
+        app.world_mut().insert_resource(CurrentLevel(level));
+        app.update();
+
+        let key = LevelKey("block", 0);
+        let mut platforms = app
+            .world_mut()
+            .query_filtered::<(Entity, &LevelKey), With<BlockDefinition>>();
+        let [(platform, platform_key)] = platforms.iter(app.world()).collect::<Vec<_>>()[..] else {
+            panic!("expected one platform");
+        };
+        assert_eq!(*platform_key, key);
+
+        // It falls out of the level, as `block::drop_falling_platforms` has
+        // it.
+        app.world_mut().despawn(platform);
+        app.world_mut()
+            .resource_mut::<LevelState>()
+            .clear(key, RestartPolicy::Reset);
+        app.update();
+        assert_eq!(platforms.iter(app.world()).count(), 0);
+
+        app.world_mut().send_event(ResetLevel(ResetCause::Death));
+        app.update();
+        assert_eq!(platforms.iter(app.world()).count(), 1);
+    }
+}
@@ -0,0 +1,141 @@
+//! Waiting for assets to load before the game starts.
+//!
+//! Anything that loads an asset the game can't start without adds its handle
+//! to [`LoadingAssets`]. That includes assets loaded as a result of other
+//! assets, such as a level's background tiles, so those systems have to run
+//! before [`check_assets`] for the progress to add up.
+
+use bevy::{
+    asset::{LoadState, UntypedAssetId},
+    prelude::*,
+    utils::HashSet,
+};
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameState {
+    #[default]
+    Loading,
+    Playing,
+}
+
+/// The assets being waited for. This is removed once the game starts, so
+/// anything loaded after that is not waited for.
+#[derive(Resource, Default)]
+pub struct LoadingAssets(Vec<UntypedHandle>);
+
+impl LoadingAssets {
+    pub fn add(&mut self, handle: impl Into<UntypedHandle>) {
+        self.0.push(handle.into());
+    }
+}
+
+/// The loading screen's progress bar.
+#[derive(Component)]
+pub struct ProgressBar;
+
+/// The line of text under the progress bar.
+#[derive(Component)]
+pub struct LoadingStatus;
+
+pub fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::BLACK),
+            // Draw over the level and characters, which spawn while loading.
+            GlobalZIndex(i32::MAX),
+            StateScoped(GameState::Loading),
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(300.0),
+                        height: Val::Px(12.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.2, 0.2, 0.2)),
+                ))
+                .with_children(|parent| {
+                    parent.spawn((
+                        Node {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.8, 0.6, 0.3)),
+                        ProgressBar,
+                    ));
+                });
+            parent.spawn((
+                Text::new("Loading..."),
+                TextFont::from_font_size(16.0),
+                TextLayout::new_with_justify(JustifyText::Center),
+                LoadingStatus,
+            ));
+        });
+}
+
+/// Show how far along loading is, and start the game once everything has
+/// loaded.
+///
+/// If anything fails to load, the game doesn't start, and the paths of the
+/// assets that failed are shown instead. With the `dev` feature, fixing the
+/// file reloads it and carries on.
+pub fn check_assets(
+    loading: Res<LoadingAssets>,
+    asset_server: Res<AssetServer>,
+    mut reported: Local<HashSet<UntypedAssetId>>,
+    mut progress_bar: Query<&mut Node, With<ProgressBar>>,
+    mut status: Query<&mut Text, With<LoadingStatus>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let mut loaded = 0;
+    let mut failed = Vec::new();
+    for handle in &loading.0 {
+        match asset_server.get_load_state(handle.id()) {
+            Some(LoadState::Loaded) => loaded += 1,
+            Some(LoadState::Failed(err)) => {
+                let path = handle
+                    .path()
+                    .map_or_else(|| format!("{:?}", handle.id()), ToString::to_string);
+                if reported.insert(handle.id()) {
+                    error!("Failed to load {path}: {err}");
+                }
+                failed.push(path);
+            }
+            _ => {}
+        }
+    }
+
+    let total = loading.0.len();
+    if loaded == total {
+        next_state.set(GameState::Playing);
+        return;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let fraction = loaded as f32 / total as f32;
+    if let Ok(mut bar) = progress_bar.get_single_mut() {
+        bar.width = Val::Percent(fraction * 100.0);
+    }
+    if let Ok(mut text) = status.get_single_mut() {
+        text.0 = if failed.is_empty() {
+            format!("Loading... {loaded}/{total}")
+        } else {
+            format!("Couldn't load:\n{}", failed.join("\n"))
+        };
+    }
+}
+
+pub fn finish_loading(mut commands: Commands) {
+    commands.remove_resource::<LoadingAssets>();
+}
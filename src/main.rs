@@ -2,80 +2,247 @@
 #![allow(clippy::needless_pass_by_value)]
 #![allow(clippy::type_complexity)]
 
+mod ai;
 mod animation;
+mod assets;
+mod background;
+mod block;
 mod camera;
+mod camera_effects;
+mod camera_zones;
 mod character;
-mod embedded_assets;
-mod level1;
+mod checkpoint;
+mod enemy;
+mod fruit;
+mod hazard;
+mod health;
+mod hud;
+mod input;
+mod level;
+mod loading;
 mod physics;
+mod pixel_perfect;
 mod platform;
+mod projectile;
 mod restart;
+mod ron_asset;
+mod tilemap;
+mod tuning;
 
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{app::RunFixedMainLoopSystem, prelude::*};
 
-use crate::{embedded_assets::EmbeddedAssetPlugin, restart::RestartableSystems};
+use crate::{
+    animation::CharacterAnimationDefinition,
+    assets::EmbeddedAssetPlugin,
+    background::BackgroundOverride,
+    camera::CameraSettings,
+    camera_effects::CameraEffect,
+    enemy::EnemyKindsDefinition,
+    fruit::{FruitCollection, FruitCount, Score},
+    health::{Damage, Kill},
+    input::Roster,
+    level::{LevelDefinition, LevelState},
+    loading::{GameState, LoadingAssets},
+    physics::Collided,
+    pixel_perfect::RenderMode,
+    projectile::{ProjectileHit, ProjectilePool, Shoot},
+    restart::ResetLevel,
+    ron_asset::RonAssetLoader,
+    tilemap::TilesetDefinition,
+    tuning::MovementTuning,
+};
 
 fn main() {
-    let mut app = App::new();
+    App::new()
+        .add_plugins((
+            DefaultPlugins.set(ImagePlugin::default_nearest()),
+            EmbeddedAssetPlugin,
+            data_assets,
+            game_state,
+            frame_systems,
+            fixed_systems,
+        ))
+        .insert_resource(RenderMode::from_args())
+        .insert_resource(BackgroundOverride::from_args())
+        .init_resource::<LoadingAssets>()
+        .init_resource::<LevelState>()
+        .init_resource::<FruitCount>()
+        .init_resource::<Score>()
+        .insert_resource(FruitCollection::load())
+        .init_resource::<Roster>()
+        .init_resource::<CameraSettings>()
+        .init_resource::<ProjectilePool>()
+        .add_event::<CameraEffect>()
+        .add_event::<Damage>()
+        .add_event::<Collided>()
+        .add_event::<Kill>()
+        .add_event::<ResetLevel>()
+        .add_event::<Shoot>()
+        .add_event::<ProjectileHit>()
+        .run();
+}
 
-    let respawnables = RestartableSystems(vec![
-        app.register_system(character::setup),
-        app.register_system(camera::reset),
-    ]);
+/// Game data that's loaded from RON files.
+fn data_assets(app: &mut App) {
+    app.init_asset::<LevelDefinition>()
+        .register_asset_loader(RonAssetLoader::<LevelDefinition>::new(&["level.ron"]))
+        .init_asset::<CharacterAnimationDefinition>()
+        .register_asset_loader(RonAssetLoader::<CharacterAnimationDefinition>::new(&[
+            "anim.ron",
+        ]))
+        .init_asset::<TilesetDefinition>()
+        .register_asset_loader(RonAssetLoader::<TilesetDefinition>::new(&["tileset.ron"]))
+        .init_asset::<EnemyKindsDefinition>()
+        .register_asset_loader(RonAssetLoader::<EnemyKindsDefinition>::new(&[
+            "enemies.ron",
+        ]))
+        .init_asset::<MovementTuning>()
+        .register_asset_loader(RonAssetLoader::<MovementTuning>::new(&["tuning.ron"]));
+}
 
-    app.add_plugins((
-        DefaultPlugins.set(ImagePlugin::default_nearest()),
-        EmbeddedAssetPlugin,
-    ))
-    .add_systems(
-        Startup,
-        (setup, camera::setup, character::setup, level1::setup),
-    )
-    .add_systems(
+/// Loading, then playing, and what's set up along the way.
+fn game_state(app: &mut App) {
+    app.init_state::<GameState>()
+        .enable_state_scoped_entities::<GameState>()
+        .add_systems(OnEnter(GameState::Loading), loading::spawn_loading_screen)
+        .add_systems(OnExit(GameState::Loading), loading::finish_loading)
+        .add_systems(
+            OnEnter(GameState::Playing),
+            (character::spawn_characters, hud::spawn_hud),
+        )
+        .add_systems(
+            Startup,
+            (
+                (
+                    camera::setup,
+                    pixel_perfect::setup.run_if(resource_equals(RenderMode::PixelPerfect)),
+                )
+                    .chain(),
+                block::setup,
+                character::setup,
+                enemy::setup,
+                fruit::setup,
+                hazard::setup,
+                level::setup,
+                tilemap::setup,
+                tuning::setup,
+            ),
+        );
+}
+
+/// What runs once a frame: loading, animation, the camera and input.
+fn frame_systems(app: &mut App) {
+    app.add_systems(
         Update,
         (
-            character::animate_character,
-            character::move_character,
-            character::jump,
-            camera::track_character,
+            // Everything that loads assets has to have had its say before
+            // checking whether they've all loaded.
+            (
+                level::spawn_level,
+                hazard::build_hazards,
+                block::build_blocks,
+                fruit::build_fruits,
+                background::spawn_background_layers,
+                tilemap::load_tileset,
+                tilemap::build_tilemaps,
+                character::load_character_animations,
+                enemy::load_enemy_kinds,
+                enemy::build_enemies,
+                tuning::apply_movement_tuning,
+                loading::check_assets.run_if(in_state(GameState::Loading)),
+            )
+                .chain(),
+            character::restart_characters.after(level::spawn_level),
+            projectile::clear_projectiles,
+            character::animate_character.after(character::load_character_animations),
+            animation::animate_loops,
+            enemy::animate_enemies,
+            fruit::vanish,
+            block::settle_blocks,
+            (fruit::count_fruits, hud::update_fruit_counter).chain(),
+            fruit::save_collection,
+            health::blink_invulnerable,
+            checkpoint::raise_flags,
+            (
+                camera::reset,
+                camera_effects::receive_camera_effects,
+                camera_zones::update_camera_zones,
+                camera::track_character,
+                camera_effects::apply_camera_effects,
+                pixel_perfect::snap_camera.run_if(resource_equals(RenderMode::PixelPerfect)),
+                background::scroll_background,
+            )
+                .chain(),
+            pixel_perfect::fit_canvas.run_if(resource_equals(RenderMode::PixelPerfect)),
+            input::handle_gamepad_connections,
+            (input::join_players, restart::restart_on_command).run_if(in_state(GameState::Playing)),
             // draw_aabb_boxes,
-            restart::respawn_restartable_on_command,
             restart::quit_on_command,
         ),
+    );
+}
+
+/// The game's simulation, which runs at a fixed rate.
+fn fixed_systems(app: &mut App) {
+    app.add_systems(
+        RunFixedMainLoop,
+        (
+            character::read_input
+                .in_set(RunFixedMainLoopSystem::BeforeFixedMainLoop)
+                .run_if(in_state(GameState::Playing)),
+            physics::interpolate_transforms.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
+        ),
     )
     .add_systems(
         FixedUpdate,
         (
-            physics::apply_velocity,
-            physics::apply_gravity,
-            physics::check_for_collisions,
+            // Decide how everything wants to move,
+            (
+                character::jump,
+                character::move_character,
+                ai::perceive,
+                ai::decide,
+                ai::act,
+                projectile::fire_projectiles,
+            )
+                .chain(),
+            // move it,
+            (
+                physics::apply_velocity,
+                hazard::move_saws,
+                projectile::move_projectiles,
+                physics::apply_gravity,
+                physics::check_for_collisions,
+            )
+                .chain(),
+            // react to where it ended up,
+            (
+                ai::sense_walls,
+                block::break_boxes,
+                block::bump_blocks,
+                block::bounce_on_trampolines,
+                block::trigger_falling_platforms,
+                block::drop_falling_platforms,
+                character::shake_on_hard_landing,
+                checkpoint::touch_checkpoints,
+                fruit::collect_fruits,
+                hazard::cycle_fire,
+                hazard::hurt_on_contact,
+                projectile::hit_with_projectiles,
+                projectile::damage_on_hit,
+                enemy::defeat_shot_enemies,
+                enemy::stomp_or_hurt,
+                enemy::knock_out,
+                level::kill_out_of_bounds,
+                enemy::despawn_fallen_enemies,
+            )
+                .chain(),
+            // and deal with whoever got hurt.
+            (health::apply_damage, health::recover, health::respawn_dead).chain(),
         )
-            .chain(),
-    )
-    .insert_resource(respawnables)
-    .run();
-}
-
-fn setup(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    query: Query<&Window, With<PrimaryWindow>>,
-) {
-    // Background
-    let window = query.single();
-    commands.spawn((
-        Sprite {
-            image: asset_server.load("embedded://remrof/../assets/bg/green.png"),
-            image_mode: SpriteImageMode::Tiled {
-                tile_x: true,
-                tile_y: true,
-                stretch_value: 1.0,
-            },
-            custom_size: Some(window.size()),
-            ..default()
-        },
-        Transform::from_translation(Vec3::new(0.0, 0.0, -1.0)),
-    ));
+            .chain()
+            .run_if(in_state(GameState::Playing)),
+    );
 }
 
 use crate::physics::{Collider, Grounded};
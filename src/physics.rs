@@ -1,5 +1,6 @@
 use bevy::math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume};
 use bevy::prelude::*;
+use serde::Deserialize;
 
 #[derive(Component, Debug)]
 pub struct Velocity {
@@ -8,6 +9,29 @@ pub struct Velocity {
     pub is_grounded: bool,
 }
 
+/// Where a moving entity is, as far as the physics is concerned.
+///
+/// Physics runs in `FixedUpdate`, which doesn't line up with the frames being
+/// drawn, so it moves this rather than the `Transform`. Every frame, the
+/// `Transform` is placed the right fraction of the way from `previous` to
+/// `current` by [`interpolate_transforms`].
+#[derive(Component, Debug)]
+pub struct PhysicsPosition {
+    /// Where the entity is as of the latest fixed step.
+    pub current: Vec2,
+    /// Where the entity was as of the fixed step before that.
+    pub previous: Vec2,
+}
+
+impl PhysicsPosition {
+    pub fn new(position: Vec2) -> Self {
+        PhysicsPosition {
+            current: position,
+            previous: position,
+        }
+    }
+}
+
 /// A Grounded entity is affected by (i.e. falls onto) platforms/the ground.
 /// It gives a way to differentiate between "dynamic" entities (e.g. the player)
 /// (which would be a "Grounded" entity) and "static" entities (e.g. the ground
@@ -27,6 +51,27 @@ pub struct Collider {
     pub offset: Vec2,
 }
 
+impl Collider {
+    /// The collider's box, for an entity at the given position.
+    pub fn aabb(&self, position: Vec2) -> Aabb2d {
+        Aabb2d::new(position + self.offset, self.size / 2.0)
+    }
+}
+
+/// Where something is as far as the simulation is concerned. Things that move,
+/// such as falling platforms, are at their physics position rather than where
+/// they were last drawn.
+pub fn simulated_position(transform: &Transform, position: Option<&PhysicsPosition>) -> Vec2 {
+    position.map_or(transform.translation.truncate(), |position| {
+        position.current
+    })
+}
+
+/// A collider that nothing bumps into. Things pass through it, and it's only
+/// used to tell when they overlap it.
+#[derive(Component)]
+pub struct Sensor;
+
 impl Default for Collider {
     fn default() -> Self {
         Collider {
@@ -36,32 +81,85 @@ impl Default for Collider {
     }
 }
 
+/// What something that can be hit, such as by a projectile, counts as.
+/// Anything solid that isn't [`Grounded`] is [`Layer::Terrain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Layer {
+    Terrain,
+    Characters,
+    Enemies,
+}
+
+impl Layer {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// A set of [`Layer`]s, written as a list of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "Vec<Layer>")]
+pub struct LayerMask(u8);
+
+impl LayerMask {
+    pub fn contains(self, layer: Layer) -> bool {
+        self.0 & layer.bit() != 0
+    }
+}
+
+impl From<Vec<Layer>> for LayerMask {
+    fn from(layers: Vec<Layer>) -> Self {
+        LayerMask(layers.into_iter().fold(0, |mask, layer| mask | layer.bit()))
+    }
+}
+
+/// Which [`Layer`] a [`Grounded`] entity is on.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct CollisionLayer(pub Layer);
+
 /// From which direction did the collision occur?
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-enum Collision {
+pub enum Collision {
     Left,
     Right,
     Top,
     Bottom,
 }
 
+/// A [`Grounded`] entity ran into a [`Collider`]. `side` is which side of the
+/// collider it's on, so [`Collision::Left`] means it walked into the
+/// collider's left side.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Collided {
+    pub entity: Entity,
+    /// What it ran into.
+    pub other: Entity,
+    pub side: Collision,
+    /// How fast it was moving when it ran into it, before being stopped.
+    pub velocity: Vec2,
+}
+
 pub fn check_for_collisions(
-    mut grounded: Query<(&mut Velocity, &mut Transform, &Collider), With<Grounded>>,
-    colliders: Query<(&Transform, &Collider), (With<Collider>, Without<Grounded>)>,
+    mut grounded: Query<(Entity, &mut Velocity, &mut PhysicsPosition, &Collider), With<Grounded>>,
+    colliders: Query<
+        (Entity, &Transform, Option<&PhysicsPosition>, &Collider),
+        (Without<Grounded>, Without<Sensor>),
+    >,
+    mut collided: EventWriter<Collided>,
 ) {
     // For every "Grounded" component, see if it is colliding with a "Collider".
     // A grounded thing can also be a collider (e.g. two players can collide
     // with each other).
-    for (mut grounded_velocity, mut grounded_transform, grounded_collider) in &mut grounded {
+    for (entity, mut grounded_velocity, mut grounded_position, grounded_collider) in &mut grounded {
         let mut is_grounded = false;
-        for (collider_transform, collider_collider) in &colliders {
+        let velocity = Vec2::new(grounded_velocity.x, grounded_velocity.y);
+        for (other, collider_transform, collider_position, collider_collider) in &colliders {
             // We need center and half-sizes for both entities.
-            let grounded_center =
-                grounded_transform.translation.truncate() + grounded_collider.offset;
+            let grounded_center = grounded_position.current + grounded_collider.offset;
             let grounded_half_size = grounded_collider.size / 2.0;
 
-            let collider_center =
-                collider_transform.translation.truncate() + collider_collider.offset;
+            let collider_center = simulated_position(collider_transform, collider_position)
+                + collider_collider.offset;
             let collider_half_size = collider_collider.size / 2.0;
 
             // Create bounding boxes for both entities.
@@ -70,34 +168,36 @@ pub fn check_for_collisions(
 
             let collision = collision_direction(&grounded_box, &collider_box);
             if let Some(collision) = collision {
+                collided.send(Collided {
+                    entity,
+                    other,
+                    side: collision,
+                    velocity,
+                });
                 match collision {
                     Collision::Left => {
                         if grounded_velocity.x > 0.0 {
                             grounded_velocity.x = 0.0;
-                            grounded_transform.translation.x =
-                                collider_box.min.x - grounded_half_size.x;
+                            grounded_position.current.x = collider_box.min.x - grounded_half_size.x;
                         }
                     }
                     Collision::Right => {
                         if grounded_velocity.x < 0.0 {
                             grounded_velocity.x = 0.0;
-                            grounded_transform.translation.x =
-                                collider_box.max.x + grounded_half_size.x;
+                            grounded_position.current.x = collider_box.max.x + grounded_half_size.x;
                         }
                     }
                     Collision::Top => {
                         if grounded_velocity.y < 0.0 {
                             grounded_velocity.y = 0.0;
-                            grounded_transform.translation.y =
-                                collider_box.max.y + grounded_half_size.y;
+                            grounded_position.current.y = collider_box.max.y + grounded_half_size.y;
                         }
                         is_grounded = true;
                     }
                     Collision::Bottom => {
                         if grounded_velocity.y > 0.0 {
                             grounded_velocity.y = 0.0;
-                            grounded_transform.translation.y =
-                                collider_box.min.y - grounded_half_size.y;
+                            grounded_position.current.y = collider_box.min.y - grounded_half_size.y;
                         }
                     }
                 }
@@ -131,19 +231,42 @@ fn collision_direction(grounded_box: &Aabb2d, collider_box: &Aabb2d) -> Option<C
     Some(side)
 }
 
-pub fn apply_velocity(mut query: Query<(&Velocity, &mut Transform)>, time: Res<Time>) {
-    for (velocity, mut transform) in &mut query {
-        transform.translation.x += velocity.x * time.delta_secs();
-        transform.translation.y += velocity.y * time.delta_secs();
+pub fn apply_velocity(mut query: Query<(&Velocity, &mut PhysicsPosition)>, time: Res<Time>) {
+    for (velocity, mut position) in &mut query {
+        position.previous = position.current;
+        position.current.x += velocity.x * time.delta_secs();
+        position.current.y += velocity.y * time.delta_secs();
     }
 }
 
-pub fn apply_gravity(mut query: Query<&mut Velocity>, time: Res<Time>) {
-    let gravity_factor = 2000.0;
+/// Nothing falls faster than this, however far it falls, so that a long fall
+/// can't move it far enough in one step to go through a platform.
+const MAX_FALL_SPEED: f32 = 1500.0;
 
+/// How fast things that fall speed up, in units per second per second.
+pub const GRAVITY: f32 = 2000.0;
+
+pub fn apply_gravity(mut query: Query<&mut Velocity>, time: Res<Time>) {
     for mut velocity in &mut query {
         if !velocity.is_grounded {
-            velocity.y -= gravity_factor * time.delta_secs();
+            velocity.y -= GRAVITY * time.delta_secs();
+            velocity.y = velocity.y.max(-MAX_FALL_SPEED);
         }
     }
 }
+
+/// Place each moving entity where it would be between the last two fixed
+/// steps, given how far through the next fixed step we are. Without this, the
+/// entity moves in steps that don't line up with the frames being drawn, and
+/// stutters.
+pub fn interpolate_transforms(
+    mut query: Query<(&PhysicsPosition, &mut Transform)>,
+    fixed_time: Res<Time<Fixed>>,
+) {
+    let fraction = fixed_time.overstep_fraction();
+    for (position, mut transform) in &mut query {
+        let rendered = position.previous.lerp(position.current, fraction);
+        transform.translation.x = rendered.x;
+        transform.translation.y = rendered.y;
+    }
+}
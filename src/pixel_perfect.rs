@@ -0,0 +1,247 @@
+//! Pixel-perfect rendering.
+//!
+//! In this mode the game is drawn to a small, fixed-size canvas in which one
+//! texture pixel is exactly one canvas pixel, and the canvas is blown up to
+//! fill the window by a whole number so that every pixel comes out the same
+//! size. Any space left over is letterboxed.
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+        },
+        view::RenderLayers,
+    },
+    window::PrimaryWindow,
+};
+
+use crate::{
+    camera::{self, CharacterCamera},
+    level::LevelBounds,
+};
+
+/// The size of the canvas the game is drawn to, in pixels.
+pub const VIRTUAL_RESOLUTION: UVec2 = UVec2::new(640, 360);
+
+/// How many world units one texture pixel takes up. Sprites are drawn at this
+/// scale.
+pub const PIXEL_SCALE: f32 = 1.5;
+
+/// The layer the canvas is drawn on, so that the game camera doesn't see it.
+const CANVAS_LAYER: RenderLayers = RenderLayers::layer(1);
+
+/// How the game is drawn to the window.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Straight to the window, at whatever size it is.
+    Window,
+    /// Through a fixed-size canvas; see the module docs.
+    PixelPerfect,
+}
+
+impl RenderMode {
+    /// Pass `--pixel-perfect` on the command line to turn it on.
+    pub fn from_args() -> Self {
+        if std::env::args().any(|arg| arg == "--pixel-perfect") {
+            RenderMode::PixelPerfect
+        } else {
+            RenderMode::Window
+        }
+    }
+
+    /// How much of the world the camera shows, in world units, before it is
+    /// zoomed.
+    pub fn view_size(self, window: &Window) -> Vec2 {
+        match self {
+            RenderMode::Window => window.size(),
+            RenderMode::PixelPerfect => VIRTUAL_RESOLUTION.as_vec2() * PIXEL_SCALE,
+        }
+    }
+}
+
+/// The sprite showing the canvas in the window.
+#[derive(Component)]
+struct Canvas;
+
+/// The camera that draws the canvas to the window.
+#[derive(Component)]
+pub struct CanvasCamera;
+
+/// Point the game camera at a canvas, and show the canvas in the window.
+pub fn setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    game_camera: Query<Entity, With<CharacterCamera>>,
+) {
+    let Ok(game_camera) = game_camera.get_single() else {
+        return;
+    };
+
+    let canvas_size = Extent3d {
+        width: VIRTUAL_RESOLUTION.x,
+        height: VIRTUAL_RESOLUTION.y,
+        ..default()
+    };
+    let mut canvas = Image {
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: canvas_size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    canvas.resize(canvas_size);
+    let canvas = images.add(canvas);
+
+    commands.entity(game_camera).insert((
+        Camera {
+            // Draw the game before the canvas that shows it.
+            order: -1,
+            target: RenderTarget::Image(canvas.clone()),
+            ..default()
+        },
+        Msaa::Off,
+    ));
+
+    commands.spawn((Sprite::from_image(canvas), Canvas, CANVAS_LAYER));
+    commands.spawn((
+        Camera2d,
+        Camera {
+            clear_color: ClearColorConfig::Custom(Color::BLACK),
+            ..default()
+        },
+        Msaa::Off,
+        CanvasCamera,
+        CANVAS_LAYER,
+    ));
+}
+
+/// Blow the canvas up by the largest whole number that fits in the window.
+///
+/// This works in physical pixels, since on a high-DPI display a whole number
+/// of logical pixels may not be a whole number of real ones.
+#[allow(clippy::cast_precision_loss)]
+pub fn fit_canvas(
+    window: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
+    mut projection: Query<&mut OrthographicProjection, With<CanvasCamera>>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    let Ok(mut projection) = projection.get_single_mut() else {
+        return;
+    };
+
+    let fits = window.physical_size() / VIRTUAL_RESOLUTION;
+    let factor = fits.min_element().max(1);
+    projection.scale = window.scale_factor() / factor as f32;
+}
+
+/// Line the camera up with the canvas's pixels, so that the world doesn't
+/// shimmer as the camera moves by fractions of a pixel.
+///
+/// This runs after the camera has been placed for the frame, and also scales
+/// the camera so that one texture pixel is one canvas pixel. Zooming is
+/// rounded to a whole number of canvas pixels per texture pixel, or texture
+/// pixels per canvas pixel, so that every pixel still comes out the same size.
+/// Since that changes how much the camera shows, the view is kept inside the
+/// level bounds again afterwards.
+pub fn snap_camera(
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<CharacterCamera>>,
+    bounds: Option<Res<LevelBounds>>,
+) {
+    let Ok((mut transform, mut projection)) = camera.get_single_mut() else {
+        return;
+    };
+
+    projection.scale = whole_pixel_ratio(projection.scale) * PIXEL_SCALE;
+    if let Some(bounds) = bounds {
+        let view_half_size = VIRTUAL_RESOLUTION.as_vec2() * projection.scale / 2.0;
+        let center =
+            camera::clamp_to_bounds(transform.translation.truncate(), view_half_size, bounds.0);
+        transform.translation = center.extend(transform.translation.z);
+    }
+
+    // One canvas pixel, in world units.
+    let pixel = projection.scale;
+    transform.translation.x = (transform.translation.x / pixel).round() * pixel;
+    transform.translation.y = (transform.translation.y / pixel).round() * pixel;
+}
+
+/// Round a zoom to a whole ratio of pixels: 2, 3 and so on when zoomed out,
+/// and 1/2, 1/3 and so on when zoomed in.
+///
+/// Zooming out is always rounded up, so that the camera shows at least as much
+/// as it was framed to, and nobody ends up off screen. Zooming in is rounded
+/// to the nearest ratio, so one that's less than halfway to 1/2, such as a
+/// camera punch, doesn't show in this mode.
+fn whole_pixel_ratio(scale: f32) -> f32 {
+    if scale < 1.0 {
+        1.0 / scale.recip().round()
+    } else {
+        scale.ceil()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn zoom_is_rounded_to_whole_pixel_ratios() {
+        assert_eq!(whole_pixel_ratio(1.0), 1.0);
+        assert_eq!(whole_pixel_ratio(1.4), 2.0);
+        assert_eq!(whole_pixel_ratio(1.6), 2.0);
+        assert_eq!(whole_pixel_ratio(0.8), 1.0);
+        assert_eq!(whole_pixel_ratio(0.55), 0.5);
+        assert_eq!(whole_pixel_ratio(0.3), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn snapping_keeps_framed_characters_on_screen_and_inside_the_bounds() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(LevelBounds(Rect::new(-1000.0, -600.0, 1000.0, 600.0)))
+            .add_systems(Update, snap_camera);
+
+        // Framed by the camera at a zoom of 1.4, which shows less than the
+        // two characters at 1.0.
+        let characters = [Vec2::new(-600.0, 0.0), Vec2::new(600.0, 0.0)];
+        let camera = app
+            .world_mut()
+            .spawn((
+                CharacterCamera,
+                Transform::from_xyz(30.0, 0.0, 0.0),
+                OrthographicProjection {
+                    scale: 1.4,
+                    ..OrthographicProjection::default_2d()
+                },
+            ))
+            .id();
+
+        app.update();
+        let world = app.world();
+        let center = world
+            .get::<Transform>(camera)
+            .unwrap()
+            .translation
+            .truncate();
+        let scale = world.get::<OrthographicProjection>(camera).unwrap().scale;
+        let view = Rect::from_center_size(center, VIRTUAL_RESOLUTION.as_vec2() * scale);
+        for character in characters {
+            assert!(view.contains(character), "{character} is off screen");
+        }
+        let bounds = world.resource::<LevelBounds>().0;
+        assert_eq!(view.union(bounds), bounds);
+    }
+}
@@ -0,0 +1,301 @@
+//! Things that are shot, and fly until they hit something or run out of time.
+//!
+//! Anything can fire a projectile by sending [`Shoot`]. What a projectile can
+//! hit is up to its [`LayerMask`], so the same system serves enemies shooting
+//! at characters and characters shooting at enemies. A hit sends a
+//! [`ProjectileHit`], which whatever was hit deals with.
+//!
+//! Projectiles come and go often, so rather than being despawned, spent ones
+//! are hidden and kept in the [`ProjectilePool`] for the next shot.
+
+use bevy::{
+    math::bounding::{Aabb2d, IntersectsVolume},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    health::{Damage, Dead},
+    level::LevelBounds,
+    physics::{
+        self, Collider, CollisionLayer, Grounded, Layer, LayerMask, PhysicsPosition, Sensor,
+        GRAVITY,
+    },
+    pixel_perfect::PIXEL_SCALE,
+    restart::ResetLevel,
+};
+
+/// A kind of projectile, as written in whatever shoots it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectileDefinition {
+    /// The path to its image under `assets/`.
+    pub texture: String,
+    /// The size of its hitbox, in the image's pixels.
+    pub size: Vec2,
+    /// How fast it's shot, in units per second.
+    pub speed: f32,
+    /// How long it flies for if it doesn't hit anything.
+    #[serde(default = "default_lifetime")]
+    pub lifetime: f32,
+    /// Whether it falls, or flies straight.
+    #[serde(default)]
+    pub gravity: bool,
+    #[serde(default = "default_damage")]
+    pub damage: u32,
+    /// What it hits. It flies through everything else, terrain included if
+    /// that isn't listed.
+    pub hits: LayerMask,
+}
+
+fn default_lifetime() -> f32 {
+    3.0
+}
+
+fn default_damage() -> u32 {
+    1
+}
+
+/// Fire a projectile.
+#[derive(Event, Debug, Clone)]
+pub struct Shoot {
+    /// Whoever fired it. It never hits them.
+    pub owner: Entity,
+    pub origin: Vec2,
+    /// Which way to shoot it. Its speed comes from the definition.
+    pub direction: Dir2,
+    pub definition: ProjectileDefinition,
+    pub texture: Handle<Image>,
+}
+
+/// A projectile hit something.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ProjectileHit {
+    /// What it hit, or `None` for the terrain.
+    pub target: Option<Entity>,
+    /// Where the projectile was when it hit.
+    pub position: Vec2,
+    pub damage: u32,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    pub owner: Entity,
+    pub velocity: Vec2,
+    pub gravity: bool,
+    pub damage: u32,
+    pub hits: LayerMask,
+    pub lifetime: Timer,
+}
+
+/// Marks a projectile that's in the [`ProjectilePool`], waiting to be shot
+/// again.
+#[derive(Component)]
+pub struct Pooled;
+
+/// Spent projectiles, ready to be reused.
+#[derive(Resource, Default)]
+pub struct ProjectilePool(Vec<Entity>);
+
+impl ProjectilePool {
+    fn recycle(&mut self, commands: &mut Commands, projectile: Entity) {
+        commands
+            .entity(projectile)
+            .insert((Pooled, Visibility::Hidden));
+        self.0.push(projectile);
+    }
+}
+
+/// Fire projectiles, reusing spent ones where there are any.
+pub fn fire_projectiles(
+    mut commands: Commands,
+    mut shots: EventReader<Shoot>,
+    mut pool: ResMut<ProjectilePool>,
+) {
+    for shot in shots.read() {
+        let definition = &shot.definition;
+        let projectile = (
+            Sprite::from_image(shot.texture.clone()),
+            Transform {
+                translation: shot.origin.extend(60.0),
+                scale: Vec3::splat(PIXEL_SCALE),
+                ..default()
+            },
+            Visibility::Inherited,
+            PhysicsPosition::new(shot.origin),
+            Collider {
+                size: definition.size * PIXEL_SCALE,
+                ..default()
+            },
+            Sensor,
+            Projectile {
+                owner: shot.owner,
+                velocity: shot.direction * definition.speed,
+                gravity: definition.gravity,
+                damage: definition.damage,
+                hits: definition.hits,
+                lifetime: Timer::from_seconds(definition.lifetime, TimerMode::Once),
+            },
+        );
+
+        match pool.0.pop() {
+            Some(entity) => {
+                commands
+                    .entity(entity)
+                    .remove::<Pooled>()
+                    .insert(projectile);
+            }
+            None => {
+                commands.spawn(projectile);
+            }
+        }
+    }
+}
+
+pub fn move_projectiles(
+    mut commands: Commands,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut PhysicsPosition), Without<Pooled>>,
+    mut pool: ResMut<ProjectilePool>,
+    time: Res<Time>,
+) {
+    for (entity, mut projectile, mut position) in &mut projectiles {
+        if projectile.lifetime.tick(time.delta()).finished() {
+            pool.recycle(&mut commands, entity);
+            continue;
+        }
+
+        if projectile.gravity {
+            projectile.velocity.y -= GRAVITY * time.delta_secs();
+        }
+        position.previous = position.current;
+        position.current += projectile.velocity * time.delta_secs();
+    }
+}
+
+/// Stop projectiles at the first thing they hit that's on one of their
+/// layers, other than whoever shot them, or when they leave the level.
+pub fn hit_with_projectiles(
+    mut commands: Commands,
+    projectiles: Query<(Entity, &Projectile, &PhysicsPosition, &Collider), Without<Pooled>>,
+    targets: Query<(Entity, &PhysicsPosition, &Collider, &CollisionLayer), Without<Dead>>,
+    solids: Query<
+        (&Transform, Option<&PhysicsPosition>, &Collider),
+        (Without<Grounded>, Without<Sensor>),
+    >,
+    bounds: Option<Res<LevelBounds>>,
+    mut pool: ResMut<ProjectilePool>,
+    mut hits: EventWriter<ProjectileHit>,
+) {
+    for (entity, projectile, position, collider) in &projectiles {
+        let area = collider.aabb(position.current);
+        let overlaps = |other: Aabb2d| other.intersects(&area);
+
+        let target = targets
+            .iter()
+            .find(|(target, target_position, target_collider, layer)| {
+                *target != projectile.owner
+                    && projectile.hits.contains(layer.0)
+                    && overlaps(target_collider.aabb(target_position.current))
+            })
+            .map(|(target, ..)| target);
+        let blocked = projectile.hits.contains(Layer::Terrain)
+            && solids.iter().any(|(transform, position, solid)| {
+                overlaps(solid.aabb(physics::simulated_position(transform, position)))
+            });
+        let outside = bounds
+            .as_ref()
+            .is_some_and(|bounds| !bounds.0.contains(position.current));
+
+        if target.is_some() || blocked {
+            hits.send(ProjectileHit {
+                target,
+                position: position.current,
+                damage: projectile.damage,
+            });
+        }
+        if target.is_some() || blocked || outside {
+            pool.recycle(&mut commands, entity);
+        }
+    }
+}
+
+/// Hurt whatever projectiles hit. Anything without health ignores it.
+pub fn damage_on_hit(mut hits: EventReader<ProjectileHit>, mut damage: EventWriter<Damage>) {
+    for hit in hits.read() {
+        if let Some(target) = hit.target {
+            damage.send(Damage {
+                target,
+                amount: hit.damage,
+                source: hit.position,
+            });
+        }
+    }
+}
+
+/// Put every projectile in flight away when the level is restarted.
+pub fn clear_projectiles(
+    mut commands: Commands,
+    mut resets: EventReader<ResetLevel>,
+    projectiles: Query<Entity, (With<Projectile>, Without<Pooled>)>,
+    mut pool: ResMut<ProjectilePool>,
+) {
+    if !resets.read().any(|ResetLevel(cause)| cause.is_restart()) {
+        return;
+    }
+
+    for entity in &projectiles {
+        pool.recycle(&mut commands, entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    fn shot() -> Shoot {
+        Shoot {
+            owner: Entity::PLACEHOLDER,
+            origin: Vec2::ZERO,
+            direction: Dir2::X,
+            definition: ron::from_str(
+                "(texture: \"\", size: (4.0, 4.0), speed: 100.0, lifetime: 0.5, hits: [])",
+            )
+            .unwrap(),
+            texture: Handle::default(),
+        }
+    }
+
+    #[test]
+    fn spent_projectiles_are_reused() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .init_resource::<ProjectilePool>()
+            .add_event::<Shoot>()
+            .add_systems(Update, (fire_projectiles, move_projectiles).chain());
+
+        app.world_mut().send_event(shot());
+        app.update();
+        let mut projectiles = app.world_mut().query::<(Entity, Has<Pooled>)>();
+        let [(first, pooled)] = projectiles.iter(app.world()).collect::<Vec<_>>()[..] else {
+            panic!("expected one projectile");
+        };
+        assert!(!pooled);
+
+        // Long enough for it to run out of time.
+        for _ in 0..6 {
+            app.update();
+        }
+        assert!(app.world().get::<Pooled>(first).is_some());
+
+        app.world_mut().send_event(shot());
+        app.update();
+        let projectiles: Vec<_> = projectiles.iter(app.world()).collect();
+        assert_eq!(projectiles, [(first, false)]);
+    }
+}
@@ -1,29 +1,78 @@
-//! Handles restarting the game. For now.
+//! Restarting the level.
 //!
-//! This will need to change and become level-specific, once levels are a thing.
+//! R restarts from the latest checkpoint, and Shift+R restarts the level from
+//! the very start, as though it had never been played. Either way, a
+//! [`ResetLevel`] is sent, and the level, the characters and the camera each
+//! reset themselves when they see it. Everybody having died sends one too, so
+//! that the level can undo whatever should be undone on a death.
+//!
+//! How much of the level goes back to how it was is up to each thing in it,
+//! through its [`RestartPolicy`].
 
-use bevy::ecs::system::SystemId;
 use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Put the level back the way it was, or some of it.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetLevel(pub ResetCause);
 
-#[derive(Resource)]
-pub struct RestartableSystems(pub Vec<SystemId>);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetCause {
+    /// Everyone goes back to the latest checkpoint, and the level goes back to
+    /// its snapshot, if it took one.
+    Restart,
+    /// Everyone goes back to the start, and the level goes back to how it was
+    /// before it was played.
+    RestartFromStart,
+    /// Everybody in the level has died. Each character goes back to the
+    /// checkpoint as it respawns, and only what resets on death goes back to
+    /// the snapshot. A character dying while somebody else is still alive
+    /// doesn't reset anything but that character.
+    Death,
+}
 
-#[derive(Component)]
-pub struct RestartRespawn;
+impl ResetCause {
+    /// Whether the characters and the camera start over.
+    pub fn is_restart(self) -> bool {
+        self != ResetCause::Death
+    }
+}
+
+/// What happens to something in a level that has been dealt with, such as a
+/// reached checkpoint, when the level is reset. Restarting from the start
+/// undoes everything, whatever its policy.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum RestartPolicy {
+    /// Goes back to how it was as of the latest snapshot on a restart or a
+    /// death.
+    #[default]
+    Reset,
+    /// Stays as it is.
+    Persist,
+    /// Goes back to how it was as of the latest snapshot when everybody has
+    /// died, but not on a restart.
+    ResetOnDeath,
+}
 
-pub fn respawn_restartable_on_command(
+impl RestartPolicy {
+    pub fn resets_on(self, cause: ResetCause) -> bool {
+        match self {
+            RestartPolicy::Reset => true,
+            RestartPolicy::Persist => cause == ResetCause::RestartFromStart,
+            RestartPolicy::ResetOnDeath => cause != ResetCause::Restart,
+        }
+    }
+}
+
+pub fn restart_on_command(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    query: Query<Entity, With<RestartRespawn>>,
-    mut commands: Commands,
-    systems: Res<RestartableSystems>,
+    mut reset: EventWriter<ResetLevel>,
 ) {
     if keyboard_input.just_pressed(KeyCode::KeyR) {
-        for entity in &query {
-            commands.entity(entity).despawn();
-        }
-
-        for system_id in &systems.0 {
-            commands.run_system(*system_id);
+        if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            reset.send(ResetLevel(ResetCause::RestartFromStart));
+        } else {
+            reset.send(ResetLevel(ResetCause::Restart));
         }
     }
 }
@@ -0,0 +1,107 @@
+//! Loading game data, such as levels, from RON files under `assets/`.
+//!
+//! Data loaded this way is an asset like any other, so with the `dev` feature
+//! it's reloaded when the file changes. Whatever uses it should watch for
+//! `AssetEvent::Modified` and apply the new version.
+
+use std::marker::PhantomData;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::de::DeserializeOwned;
+
+/// Loads an `A` from RON files with any of the given extensions.
+pub struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    asset: PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetLoader<A> {
+    /// Extensions are given without the leading dot, e.g. `"level.ron"`.
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        RonAssetLoader {
+            extensions,
+            asset: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RonAssetError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl std::fmt::Display for RonAssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RonAssetError::Io(err) => write!(f, "couldn't read file: {err}"),
+            RonAssetError::Ron(err) => write!(f, "couldn't parse RON: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RonAssetError {}
+
+impl From<std::io::Error> for RonAssetError {
+    fn from(err: std::io::Error) -> Self {
+        RonAssetError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for RonAssetError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        RonAssetError::Ron(err)
+    }
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = RonAssetError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<A, RonAssetError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        animation::CharacterAnimationDefinition, enemy::EnemyKindsDefinition,
+        level::LevelDefinition, tilemap::TilesetDefinition, tuning::MovementTuning,
+    };
+
+    #[test]
+    fn shipped_assets_parse() {
+        ron::from_str::<LevelDefinition>(include_str!("../assets/levels/level1.level.ron"))
+            .unwrap();
+        for animation in [
+            include_str!("../assets/animations/ninja_frog.anim.ron"),
+            include_str!("../assets/animations/mask_dude.anim.ron"),
+            include_str!("../assets/animations/pink_man.anim.ron"),
+            include_str!("../assets/animations/virtual_guy.anim.ron"),
+        ] {
+            ron::from_str::<CharacterAnimationDefinition>(animation).unwrap();
+        }
+        ron::from_str::<TilesetDefinition>(include_str!("../assets/tilesets/terrain.tileset.ron"))
+            .unwrap();
+        ron::from_str::<MovementTuning>(include_str!("../assets/tuning/movement.tuning.ron"))
+            .unwrap();
+        ron::from_str::<EnemyKindsDefinition>(include_str!("../assets/enemies/kinds.enemies.ron"))
+            .unwrap();
+    }
+}
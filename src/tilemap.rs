@@ -0,0 +1,368 @@
+//! Terrain drawn from a tileset, laid out on a grid in the level file.
+//!
+//! Each tile picks its picture from which of its neighbors are solid, so a
+//! level only has to say where the ground is. Tiles are drawn a chunk at a
+//! time, each chunk being a single mesh, and the physics gets one collider per
+//! rectangle of solid tiles rather than one per tile.
+
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+use serde::Deserialize;
+
+use crate::{
+    assets::asset_path, loading::LoadingAssets, physics::Collider, pixel_perfect::PIXEL_SCALE,
+    platform::Platform,
+};
+
+/// How many tiles wide and high each chunk is.
+const CHUNK_SIZE: usize = 16;
+
+/// A tileset, as written in its `.tileset.ron` file.
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct TilesetDefinition {
+    /// The image's path under `assets/`.
+    pub texture: String,
+    /// The width and height of every tile, in pixels.
+    pub tile_size: u32,
+    pub columns: u32,
+    pub rows: u32,
+    /// The tile to draw for each of the 16 combinations of solid neighbors;
+    /// see [`TileMap::neighbors`].
+    pub by_neighbors: Vec<u32>,
+}
+
+/// The definition [`Tileset`] is built from.
+#[derive(Resource)]
+pub struct TilesetHandle(Handle<TilesetDefinition>);
+
+/// The terrain tileset, ready to draw with.
+#[derive(Resource)]
+pub struct Tileset {
+    material: Handle<ColorMaterial>,
+    tile_size: u32,
+    columns: u32,
+    rows: u32,
+    by_neighbors: [u32; 16],
+}
+
+impl Tileset {
+    /// How big a tile is in the world.
+    #[allow(clippy::cast_precision_loss)]
+    fn world_size(&self) -> f32 {
+        self.tile_size as f32 * PIXEL_SCALE
+    }
+}
+
+/// A grid of terrain.
+#[derive(Component, Debug, Clone, Deserialize)]
+pub struct TileMap {
+    /// Where the bottom-left corner of the grid is.
+    pub origin: Vec2,
+    /// The grid, top row first, with `#` for solid tiles and anything else
+    /// for empty ones.
+    pub rows: Vec<String>,
+}
+
+/// A rectangle of tiles, counting rows up from the bottom.
+#[derive(Debug, PartialEq, Eq)]
+struct TileRect {
+    column: usize,
+    row: usize,
+    width: usize,
+    height: usize,
+}
+
+impl TileMap {
+    fn width(&self) -> usize {
+        self.rows.iter().map(String::len).max().unwrap_or(0)
+    }
+
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether a tile is solid, counting rows up from the bottom. Everything
+    /// outside the grid is empty.
+    fn is_solid(&self, column: usize, row: usize) -> bool {
+        let Some(line) = self.height().checked_sub(row + 1) else {
+            return false;
+        };
+        self.rows[line].as_bytes().get(column) == Some(&b'#')
+    }
+
+    /// Which of a tile's neighbors are solid, adding up 1 for above, 2 for
+    /// right, 4 for below and 8 for left.
+    fn neighbors(&self, column: usize, row: usize) -> usize {
+        let above = self.is_solid(column, row + 1);
+        let right = self.is_solid(column + 1, row);
+        let below = row
+            .checked_sub(1)
+            .is_some_and(|row| self.is_solid(column, row));
+        let left = column
+            .checked_sub(1)
+            .is_some_and(|column| self.is_solid(column, row));
+
+        usize::from(above)
+            | usize::from(right) << 1
+            | usize::from(below) << 2
+            | usize::from(left) << 3
+    }
+
+    /// Cover the solid tiles with as few rectangles as is easy to find:
+    /// starting from each tile not covered yet, grow a rectangle as wide as it
+    /// goes, then as tall as that width allows.
+    fn solid_rectangles(&self) -> Vec<TileRect> {
+        let (width, height) = (self.width(), self.height());
+        let mut covered = vec![false; width * height];
+        let free = |covered: &[bool], column, row| {
+            self.is_solid(column, row) && !covered[row * width + column]
+        };
+
+        let mut rectangles = Vec::new();
+        for row in 0..height {
+            for column in 0..width {
+                if !free(&covered, column, row) {
+                    continue;
+                }
+
+                let mut rect_width = 1;
+                while column + rect_width < width && free(&covered, column + rect_width, row) {
+                    rect_width += 1;
+                }
+                let mut rect_height = 1;
+                while row + rect_height < height
+                    && (column..column + rect_width)
+                        .all(|column| free(&covered, column, row + rect_height))
+                {
+                    rect_height += 1;
+                }
+
+                for covered_row in row..row + rect_height {
+                    let start = covered_row * width + column;
+                    covered[start..start + rect_width].fill(true);
+                }
+                rectangles.push(TileRect {
+                    column,
+                    row,
+                    width: rect_width,
+                    height: rect_height,
+                });
+            }
+        }
+        rectangles
+    }
+
+    /// The mesh for one chunk of the map, or nothing if the chunk is empty.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    fn chunk_mesh(&self, tileset: &Tileset, chunk_column: usize, chunk_row: usize) -> Option<Mesh> {
+        let tile_size = tileset.world_size();
+        let tile_uv = Vec2::new(1.0 / tileset.columns as f32, 1.0 / tileset.rows as f32);
+
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        let mut indices = Vec::new();
+        let rows = chunk_row * CHUNK_SIZE..((chunk_row + 1) * CHUNK_SIZE).min(self.height());
+        for row in rows {
+            let columns =
+                chunk_column * CHUNK_SIZE..((chunk_column + 1) * CHUNK_SIZE).min(self.width());
+            for column in columns {
+                if !self.is_solid(column, row) {
+                    continue;
+                }
+
+                let tile = tileset.by_neighbors[self.neighbors(column, row)];
+                let uv = Vec2::new(
+                    (tile % tileset.columns) as f32,
+                    (tile / tileset.columns) as f32,
+                ) * tile_uv;
+                let min = self.origin + Vec2::new(column as f32, row as f32) * tile_size;
+                let max = min + tile_size;
+
+                let first = positions.len() as u32;
+                positions.extend([
+                    [min.x, min.y, 0.0],
+                    [max.x, min.y, 0.0],
+                    [max.x, max.y, 0.0],
+                    [min.x, max.y, 0.0],
+                ]);
+                // Images count down from the top, unlike the world.
+                uvs.extend([
+                    [uv.x, uv.y + tile_uv.y],
+                    [uv.x + tile_uv.x, uv.y + tile_uv.y],
+                    [uv.x + tile_uv.x, uv.y],
+                    [uv.x, uv.y],
+                ]);
+                indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+            }
+        }
+
+        if positions.is_empty() {
+            return None;
+        }
+        Some(
+            Mesh::new(
+                PrimitiveTopology::TriangleList,
+                RenderAssetUsages::RENDER_WORLD,
+            )
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+            .with_inserted_indices(Indices::U32(indices)),
+        )
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut loading: ResMut<LoadingAssets>,
+) {
+    let tileset = asset_server.load(asset_path("tilesets/terrain.tileset.ron"));
+    loading.add(tileset.clone());
+    commands.insert_resource(TilesetHandle(tileset));
+}
+
+/// Build the [`Tileset`] once its definition has loaded, and again whenever
+/// it changes.
+pub fn load_tileset(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<TilesetDefinition>>,
+    handle: Res<TilesetHandle>,
+    definitions: Res<Assets<TilesetDefinition>>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    loading: Option<ResMut<LoadingAssets>>,
+) {
+    let changed = events
+        .read()
+        .any(|event| event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0));
+    if !changed {
+        return;
+    }
+    let Some(definition) = definitions.get(&handle.0) else {
+        return;
+    };
+    let Ok(by_neighbors) = definition.by_neighbors.as_slice().try_into() else {
+        error!(
+            "A tileset needs 16 tiles in by_neighbors, one for each combination of neighbors, \
+             but it has {}.",
+            definition.by_neighbors.len()
+        );
+        return;
+    };
+
+    let image = asset_server.load(asset_path(definition.texture.clone()));
+    if let Some(mut loading) = loading {
+        loading.add(image.clone());
+    }
+
+    commands.insert_resource(Tileset {
+        material: materials.add(image),
+        tile_size: definition.tile_size,
+        columns: definition.columns,
+        rows: definition.rows,
+        by_neighbors,
+    });
+}
+
+/// Spawn the chunks and colliders for new tile maps, and for every tile map
+/// when the tileset changes.
+#[allow(clippy::cast_precision_loss)]
+pub fn build_tilemaps(
+    mut commands: Commands,
+    tileset: Option<Res<Tileset>>,
+    maps: Query<(Entity, Ref<TileMap>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let Some(tileset) = tileset else {
+        return;
+    };
+
+    for (entity, map) in &maps {
+        if !tileset.is_changed() && !map.is_added() {
+            continue;
+        }
+
+        // The map stays where the world's origin is, so that the colliders'
+        // transforms are also where they are in the world.
+        commands
+            .entity(entity)
+            .despawn_descendants()
+            .insert((Transform::default(), Visibility::default()))
+            .with_children(|parent| {
+                for chunk_row in 0..map.height().div_ceil(CHUNK_SIZE) {
+                    for chunk_column in 0..map.width().div_ceil(CHUNK_SIZE) {
+                        if let Some(mesh) = map.chunk_mesh(&tileset, chunk_column, chunk_row) {
+                            parent.spawn((
+                                Mesh2d(meshes.add(mesh)),
+                                MeshMaterial2d(tileset.material.clone()),
+                            ));
+                        }
+                    }
+                }
+
+                let tile_size = tileset.world_size();
+                for rect in map.solid_rectangles() {
+                    let min =
+                        map.origin + Vec2::new(rect.column as f32, rect.row as f32) * tile_size;
+                    let size = Vec2::new(rect.width as f32, rect.height as f32) * tile_size;
+                    parent.spawn((
+                        Transform::from_translation((min + size / 2.0).extend(0.0)),
+                        Platform,
+                        Collider { size, ..default() },
+                    ));
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(rows: &[&str]) -> TileMap {
+        TileMap {
+            origin: Vec2::ZERO,
+            rows: rows.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn neighbors_count_up_from_the_bottom() {
+        let map = map(&[
+            ".#.", //
+            "###", //
+            "...", //
+        ]);
+
+        assert_eq!(map.neighbors(1, 1), 1 | 2 | 8);
+        assert_eq!(map.neighbors(1, 2), 4);
+        assert_eq!(map.neighbors(0, 1), 2);
+        assert_eq!(map.neighbors(0, 0), 1);
+    }
+
+    #[test]
+    fn solid_tiles_merge_into_rectangles() {
+        let map = map(&[
+            "##..#", //
+            "##..#", //
+            "#####", //
+        ]);
+
+        let rectangles = map.solid_rectangles();
+        let covered: usize = rectangles.iter().map(|rect| rect.width * rect.height).sum();
+        assert_eq!(covered, 11);
+        assert_eq!(rectangles.len(), 3);
+        assert_eq!(
+            rectangles[0],
+            TileRect {
+                column: 0,
+                row: 0,
+                width: 5,
+                height: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Numbers for tweaking how the game feels, loaded from `.tuning.ron` files
+//! under `assets/tuning` so that they can be changed while the game runs.
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{assets::asset_path, loading::LoadingAssets};
+
+/// How the characters move.
+#[derive(Asset, TypePath, Resource, Debug, Clone, Deserialize)]
+pub struct MovementTuning {
+    /// Top speed, in units per second.
+    pub running_speed: f32,
+    /// How fast a character leaves the ground when it jumps.
+    pub jump_velocity: f32,
+    pub ground: Control,
+    pub air: Control,
+}
+
+/// How quickly a character speeds up and slows down, in units per second per
+/// second.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Control {
+    pub acceleration: f32,
+    pub deceleration: f32,
+}
+
+/// The file the [`MovementTuning`] resource is kept up to date with.
+#[derive(Resource)]
+pub struct MovementTuningHandle(Handle<MovementTuning>);
+
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut loading: ResMut<LoadingAssets>,
+) {
+    let tuning = asset_server.load(asset_path("tuning/movement.tuning.ron"));
+    loading.add(tuning.clone());
+    commands.insert_resource(MovementTuningHandle(tuning));
+}
+
+/// Copy the tuning into its resource once it has loaded, and again whenever
+/// it changes.
+pub fn apply_movement_tuning(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<MovementTuning>>,
+    handle: Res<MovementTuningHandle>,
+    tunings: Res<Assets<MovementTuning>>,
+) {
+    let changed = events
+        .read()
+        .any(|event| event.is_loaded_with_dependencies(&handle.0) || event.is_modified(&handle.0));
+    if !changed {
+        return;
+    }
+
+    if let Some(tuning) = tunings.get(&handle.0) {
+        commands.insert_resource(tuning.clone());
+    }
+}